@@ -1,9 +1,60 @@
+use anyhow::{bail, Context};
 use std::env;
-use std::path::Path;
-use td2_map::track_structures::parse_track_structure_prefabs;
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let path = &args.get(1).expect("Missing path argument");
-    parse_track_structure_prefabs(Path::new(path)).unwrap();
-}
\ No newline at end of file
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use td2_map::track_structures::{extract_catalogue, GameVersion, PrefabExtraction, TrackStructureCatalogues};
+
+/// Extracts the track structures from the prefabs of the game and writes them as a catalogue.
+/// The game version is required, as the built-in catalogues are kept per version.
+///
+/// Usage: `prefabs <prefab directory> --game-version <version> [--output <file.json>] [--rust <file.rs>]`
+fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let path = PathBuf::from(args.next().context("Missing path argument")?);
+    let mut output = PathBuf::from("track_structures.json");
+    let mut rust_output: Option<PathBuf> = None;
+    let mut game_version: Option<GameVersion> = None;
+    while let Some(arg) = args.next() {
+        let value = args.next().with_context(|| format!("Missing value of {arg}"))?;
+        match arg.as_str() {
+            "--output" => output = PathBuf::from(value),
+            "--rust" => rust_output = Some(PathBuf::from(value)),
+            "--game-version" => game_version = Some(value.parse()?),
+            _ => bail!("Unknown argument {arg}"),
+        }
+    }
+    let game_version = game_version.context("Missing --game-version argument")?;
+
+    let builtin_catalogues = TrackStructureCatalogues::builtin();
+    let builtin_catalogue = builtin_catalogues.for_version(&game_version);
+
+    let builtin_version = builtin_catalogue.game_version.as_ref().map(|x| x.to_string()).unwrap_or_default();
+
+    let PrefabExtraction { mut catalogue, skipped, failed, copied } = extract_catalogue(Path::new(&path), builtin_catalogue)?;
+    for name in &skipped {
+        println!("No track structure in prefab {name}");
+    }
+    for (name, reason) in &failed {
+        if copied.contains(name) {
+            println!("Failed to extract prefab {name}, copied it from the built-in catalogue {builtin_version}: {reason}");
+        } else {
+            println!("Failed to extract prefab {name}: {reason}");
+        }
+    }
+    catalogue.game_version = Some(game_version.clone());
+
+    catalogue.to_json(BufWriter::new(File::create(&output)?))?;
+    if let Some(rust_output) = rust_output {
+        catalogue.to_rust(BufWriter::new(File::create(rust_output)?))?;
+    }
+
+    println!("Changes compared to the built-in catalogue {builtin_version}:");
+    print!("{}", builtin_catalogue.diff(&catalogue).with_copied(&copied));
+
+    let missing = failed.len() - copied.len();
+    if missing > 0 {
+        bail!("{missing} prefabs couldn't be extracted and are missing from {}", output.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::parse::{parse_position, parse_transform};
+use crate::transform::Transform;
+use anyhow::ensure;
+use glam::{Mat3, Vec3};
+
+/// Horizontal field of view assumed for the game camera
+static FIELD_OF_VIEW_DEG: f32 = 60.0;
+/// The camera must look down at least this much for the view to be centred where it looks
+static MIN_DOWNWARD_LOOK: f32 = 0.1;
+static MIN_VIEW_WIDTH: f32 = 100.0;
+
+/// Camera placement from a "MainCamera" or "CameraHome" row
+#[derive(Debug, Copy, Clone)]
+pub struct Camera {
+    pub pos: Vec3,
+    pub rotation: Mat3,
+}
+
+/// Area of the map seen from a camera
+#[derive(Debug, Copy, Clone)]
+pub struct Viewport {
+    /// Point on the ground in the centre of the view
+    pub center: Vec3,
+    /// Width of the visible area
+    pub width: f32,
+}
+
+impl Camera {
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        self.pos = transform.apply_to_pos(self.pos);
+        self.rotation = transform.apply_to_rotation(self.rotation);
+    }
+
+    /// Returns the area seen by the camera, assuming flat ground at the height `ground_y`.
+    /// If the camera looks down, the view is centred where it looks at the ground,
+    /// otherwise directly below the camera.
+    pub fn viewport(&self, ground_y: f32) -> Viewport {
+        let forward = self.rotation * Vec3::Z;
+        let height = (self.pos.y - ground_y).max(0.0);
+        let (center, distance) = if forward.y < -MIN_DOWNWARD_LOOK {
+            let distance = height / -forward.y;
+            (self.pos + forward * distance, distance)
+        } else {
+            (Vec3::new(self.pos.x, ground_y, self.pos.z), height)
+        };
+        let width = 2.0 * distance * (FIELD_OF_VIEW_DEG.to_radians() / 2.0).tan();
+        Viewport { center, width: width.max(MIN_VIEW_WIDTH) }
+    }
+}
+
+// MainCamera;x;y;z;rot x;rot y;rot z
+// CameraHome;x;y;z;rot x;rot y;rot z
+pub(crate) fn parse_camera(cells: &[&str]) -> anyhow::Result<Camera> {
+    ensure!(cells.len() >= 7);
+    Ok(Camera {
+        pos: parse_position(&cells[1..4])?,
+        rotation: parse_transform(&cells[4..7])?,
+    })
+}
@@ -0,0 +1,108 @@
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::parse::{ParseResult, Track};
+use crate::track_graph::{Endpoint, TrackEnd, TrackGraph};
+
+/// Components without a route exit and shorter than this are reported as orphaned fragments
+static MAX_FRAGMENT_LENGTH: f32 = 100.0;
+
+/// A set of tracks connected to each other, but not to any other track
+#[derive(Debug, Clone)]
+pub struct Component {
+    /// Indexes in `ParseResult::tracks`
+    pub track_indexes: Vec<usize>,
+    /// Sum of the lengths of the tracks
+    pub length: f32,
+    /// Indexes in `ParseResult::routes` of the routes leaving the station from this component
+    pub routes: Vec<usize>,
+}
+
+impl Component {
+    pub fn size(&self) -> usize {
+        self.track_indexes.len()
+    }
+
+    pub fn has_route_exit(&self) -> bool {
+        !self.routes.is_empty()
+    }
+
+    /// Small component which is not reachable from outside the station
+    pub fn is_orphaned_fragment(&self) -> bool {
+        !self.has_route_exit() && self.length < MAX_FRAGMENT_LENGTH
+    }
+}
+
+/// Splits the tracks into connected components, sorted from the largest one.
+/// Unusable tracks of track structures are not part of any component.
+pub fn find_components(tracks: &[Track], graph: &TrackGraph) -> Vec<Component> {
+    let mut component_of: Vec<Option<usize>> = vec![None; tracks.len()];
+    let mut components: Vec<Component> = vec![];
+
+    for first_index in 0..tracks.len() {
+        if component_of[first_index].is_some() || tracks[first_index].is_unusable() {
+            continue;
+        }
+        let component_index = components.len();
+        let mut component = Component { track_indexes: vec![], length: 0.0, routes: vec![] };
+        let mut stack = vec![first_index];
+        component_of[first_index] = Some(component_index);
+        while let Some(track_index) = stack.pop() {
+            let track = &tracks[track_index];
+            component.track_indexes.push(track_index);
+            component.length += track.length();
+            if let Some(route) = track.route {
+                if !component.routes.contains(&route) {
+                    component.routes.push(route);
+                }
+            }
+            for end in [TrackEnd::Start, TrackEnd::End] {
+                let node = &graph.nodes()[graph.node_of(Endpoint { track_index, end })];
+                for endpoint in &node.endpoints {
+                    if component_of[endpoint.track_index].is_none() {
+                        component_of[endpoint.track_index] = Some(component_index);
+                        stack.push(endpoint.track_index);
+                    }
+                }
+            }
+        }
+        component.track_indexes.sort();
+        components.push(component);
+    }
+
+    components.sort_by(|a, b| b.size().cmp(&a.size()).then(b.length.total_cmp(&a.length)));
+    components
+}
+
+/// Warns about every orphaned fragment, unless the scenery has a single component
+pub(crate) fn check_components(tracks: &[Track], graph: &TrackGraph) -> Vec<Diagnostic> {
+    let components = find_components(tracks, graph);
+    if components.len() < 2 {
+        return vec![];
+    }
+    components
+        .iter()
+        .filter(|x| x.is_orphaned_fragment())
+        .map(|component| {
+            let first = &tracks[component.track_indexes[0]];
+            let message = if component.size() == 1 {
+                format!("Track {} is not connected to any other track", first.ids.own)
+            } else {
+                let ids: Vec<String> = component.track_indexes.iter().map(|x| tracks[*x].ids.own.to_string()).collect();
+                format!(
+                    "The tracks {} form a fragment of {} m not connected to the rest of the network",
+                    ids.join(", "),
+                    component.length,
+                )
+            };
+            Diagnostic::warning(message)
+                .at_line(first.line)
+                .with_id(Some(first.ids.own))
+                .with_kind(DiagnosticKind::OrphanedFragment)
+        })
+        .collect()
+}
+
+impl ParseResult {
+    pub fn components(&self) -> Vec<Component> {
+        find_components(&self.tracks, &self.track_graph())
+    }
+}
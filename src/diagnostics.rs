@@ -0,0 +1,109 @@
+use serde::Serialize;
+use std::io::Write;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Category of the problems found by the checks on the whole scenery, as opposed to parse errors
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// A track links to another one, which doesn't link back
+    OneWayLink,
+    /// A track links to an ID which doesn't exist
+    DanglingLink,
+    /// A track links to itself
+    SelfLoop,
+    /// A branch of a switch is not connected to anything
+    MissingBranch,
+    /// The ends of connected tracks are too far apart
+    JointGap,
+    /// Connected tracks meet at different heights
+    JointHeightStep,
+    /// Connected tracks meet at an angle
+    JointKink,
+    /// A small group of tracks, not connected to the rest of the network or to any route
+    OrphanedFragment,
+}
+
+/// A single problem found while reading a scenery file
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line number in the scenery file
+    pub line: Option<usize>,
+    /// Kind of the row the problem was found in, e.g. "Track" or "TrackStructure"
+    pub row_kind: Option<String>,
+    /// ID of the track or track structure the problem refers to
+    pub id: Option<i32>,
+    pub kind: Option<DiagnosticKind>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            line: None,
+            row_kind: None,
+            id: None,
+            kind: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub(crate) fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub(crate) fn at_line(mut self, line: Option<usize>) -> Self {
+        self.line = line;
+        self
+    }
+
+    pub(crate) fn with_row_kind(mut self, row_kind: &str) -> Self {
+        self.row_kind = Some(row_kind.to_string());
+        self
+    }
+
+    pub(crate) fn with_id(mut self, id: Option<i32>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub(crate) fn with_kind(mut self, kind: DiagnosticKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    errors: usize,
+    warnings: usize,
+    diagnostics: &'a [Diagnostic],
+}
+
+pub fn count_severity(diagnostics: &[Diagnostic], severity: Severity) -> usize {
+    diagnostics.iter().filter(|x| x.severity == severity).count()
+}
+
+/// Writes the diagnostics as a JSON object with `errors` and `warnings` counts
+/// and the full `diagnostics` list.
+pub fn write_json_report<W: Write>(diagnostics: &[Diagnostic], writer: W) -> anyhow::Result<()> {
+    let report = Report {
+        errors: count_severity(diagnostics, Severity::Error),
+        warnings: count_severity(diagnostics, Severity::Warning),
+        diagnostics,
+    };
+    serde_json::to_writer_pretty(writer, &report)?;
+    Ok(())
+}
@@ -1,4 +1,18 @@
+pub mod camera;
+pub mod components;
+pub mod diagnostics;
 pub mod track_structures;
 pub(crate) mod math;
+pub mod misc;
 pub mod parse;
-pub mod svg;
\ No newline at end of file
+pub mod repair;
+pub mod sampling;
+pub mod scenery_file;
+pub mod svg;
+pub mod terrain;
+pub mod topology;
+pub mod track_graph;
+pub mod track_objects;
+pub mod track_path;
+pub mod transform;
+pub mod wires;
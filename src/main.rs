@@ -1,15 +1,18 @@
 use anyhow::bail;
 use indicatif::ParallelProgressIterator;
-use parse::parse;
+use parse::{parse_with_options, ParseOptions};
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use std::fs;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use td2_map::diagnostics::{write_json_report, Diagnostic};
 use td2_map::parse;
-use td2_map::svg::create_svg;
+use td2_map::repair::propose_repairs;
+use td2_map::svg::{create_svg_with_options, SvgOptions};
+use td2_map::track_structures::{TrackStructureCatalogue, TrackStructureCatalogues};
 
-fn process_scenery(dir: &Path) -> anyhow::Result<()> {
+fn process_scenery(dir: &Path, parse_options: &ParseOptions) -> anyhow::Result<()> {
     let Some(filename) = dir.file_name() else {
         bail!("Failed to get file name from path: {}", dir.display());
     };
@@ -17,15 +20,40 @@ fn process_scenery(dir: &Path) -> anyhow::Result<()> {
         anyhow::anyhow!("Failed to convert file name to string: {}", filename.to_string_lossy())
     })?;
     let file = File::open(dir.join( format!("{name}.sc")))?;
-    let parse_result = parse(file)?;
+    let mut parse_result = parse_with_options(file, parse_options)?;
     let output_path = PathBuf::from(format!("output/{name}.svg"));
-    create_svg(&parse_result, &output_path)?;
+    let options = SvgOptions {
+        hillshade: true,
+        signals: true,
+        misc_objects: true,
+        electrification: true,
+        ..Default::default()
+    };
+    create_svg_with_options(&parse_result, &output_path, &options)?;
+    let repairs = propose_repairs(&parse_result);
+    if !repairs.is_empty() {
+        match parse_result.apply_repairs(&repairs) {
+            Ok(()) => parse_result.write(File::create(format!("output/{name}.repaired.sc"))?)?,
+            // Some of the rows may be changed already, so no repaired file is written
+            Err(e) => parse_result.diagnostics.push(Diagnostic::error(format!("Failed to apply the repairs: {e:#}"))),
+        }
+    }
+    let report_file = File::create(format!("output/{name}.diagnostics.json"))?;
+    write_json_report(&parse_result.diagnostics, report_file)?;
     Ok(())
 }
 
 fn main() {
     let input_dir = "/home/dkgl/Documents/TTSK/TrainDriver2/SavedStations";
     fs::create_dir_all("output").unwrap();
+    // Custom track structures of modded sceneries, replacing the built-in ones with the same names
+    let overrides_path = Path::new(input_dir).join("track_structures.json");
+    let mut catalogues = TrackStructureCatalogues::builtin();
+    if overrides_path.exists() {
+        let overrides = TrackStructureCatalogue::from_json(File::open(&overrides_path).unwrap()).unwrap();
+        catalogues = catalogues.with_overrides(overrides);
+    }
+    let parse_options = ParseOptions { catalogues, ..Default::default() };
     let directories: Vec<PathBuf> =  fs::read_dir(input_dir).unwrap()
         .filter_map(|entry| {
             let entry = entry.ok()?;
@@ -41,6 +69,6 @@ fn main() {
         .par_iter()
         .progress_count(directories.len() as u64)
         .for_each(|entry| {
-            process_scenery(&entry).unwrap();
+            process_scenery(entry, &parse_options).unwrap();
         });
 }
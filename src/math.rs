@@ -61,6 +61,11 @@ impl RotatedCircle {
         self.start_rotation
     }
 
+    /// Returns the same circle with `rotation` applied before the start rotation
+    pub(crate) fn rotated(&self, rotation: Mat3) -> Self {
+        Self::new(self.original_radius, rotation * self.start_rotation)
+    }
+
     pub(crate) fn move_by_angle(&self, start: Vec3, angle: f32) -> Checkpoint {
         let radius_vec = self.original_radius * Vec3::X;
         let inner_rotation = self.inner_rotation(angle);
@@ -72,6 +77,15 @@ impl RotatedCircle {
     }
 }
 
+/// Returns the rotation turning `Vec3::Z` into the direction of `direction`, without any roll.
+/// Returns `None` for zero and vertical directions.
+pub(crate) fn rotation_from_direction(direction: Vec3) -> Option<Mat3> {
+    let z_axis = direction.try_normalize()?;
+    let x_axis = Vec3::Y.cross(z_axis).try_normalize()?;
+    let y_axis = z_axis.cross(x_axis);
+    Some(Mat3::from_cols(x_axis, y_axis, z_axis))
+}
+
 fn transform(input: Vec2) -> Vec2  {
     Vec2::new(-input.y, -input.x)
 }
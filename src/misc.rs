@@ -0,0 +1,139 @@
+use crate::parse::{parse_position, parse_transform};
+use crate::transform::Transform;
+use anyhow::ensure;
+
+/// A scenery object from a "Misc" row, e.g. a building or a platform
+#[derive(Debug, Clone)]
+pub struct MiscObject {
+    pub id: i32,
+    pub prefab: String,
+    /// Transform relative to the containing group
+    pub local: Transform,
+    /// Transform with all the containing groups applied
+    pub world: Transform,
+    /// Line of the "Misc" row
+    pub line: usize,
+}
+
+/// Objects between a "MiscGroup" row and the matching "EndMiscGroup"
+#[derive(Debug, Clone)]
+pub struct MiscGroup {
+    pub id: i32,
+    pub name: String,
+    /// Transform relative to the containing group
+    pub local: Transform,
+    /// Transform with all the containing groups applied
+    pub world: Transform,
+    /// Line of the "MiscGroup" row
+    pub line: usize,
+    pub children: Vec<MiscNode>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MiscNode {
+    Object(MiscObject),
+    Group(MiscGroup),
+}
+
+impl MiscNode {
+    /// Applies `transform` on top of the world transforms of the node and all its children
+    fn transform(&mut self, transform: &Transform) {
+        match self {
+            MiscNode::Object(object) => object.world = transform.then_apply(&object.world),
+            MiscNode::Group(group) => {
+                group.world = transform.then_apply(&group.world);
+                for child in &mut group.children {
+                    child.transform(transform);
+                }
+            },
+        }
+    }
+
+    fn collect_objects<'a>(&'a self, output: &mut Vec<&'a MiscObject>) {
+        match self {
+            MiscNode::Object(object) => output.push(object),
+            MiscNode::Group(group) => {
+                for child in &group.children {
+                    child.collect_objects(output);
+                }
+            },
+        }
+    }
+}
+
+/// Tree of misc objects, with the groups that are still open while parsing
+#[derive(Debug, Clone, Default)]
+pub struct MiscTree {
+    pub roots: Vec<MiscNode>,
+    open_groups: Vec<MiscGroup>,
+}
+
+impl MiscTree {
+    /// All the objects in the tree, depth-first
+    pub fn objects(&self) -> Vec<&MiscObject> {
+        let mut objects = vec![];
+        for root in &self.roots {
+            root.collect_objects(&mut objects);
+        }
+        objects
+    }
+
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        for root in &mut self.roots {
+            root.transform(transform);
+        }
+    }
+
+    fn parent_world(&self) -> Transform {
+        self.open_groups.last().map(|x| x.world).unwrap_or_default()
+    }
+
+    fn push_node(&mut self, node: MiscNode) {
+        match self.open_groups.last_mut() {
+            Some(group) => group.children.push(node),
+            None => self.roots.push(node),
+        }
+    }
+
+    pub(crate) fn add_object(&mut self, cells: &[&str], line: usize) -> anyhow::Result<()> {
+        let (id, prefab, local) = parse_misc_row(cells)?;
+        let world = self.parent_world().then_apply(&local);
+        self.push_node(MiscNode::Object(MiscObject { id, prefab, local, world, line }));
+        Ok(())
+    }
+
+    pub(crate) fn open_group(&mut self, cells: &[&str], line: usize) -> anyhow::Result<()> {
+        let (id, name, local) = parse_misc_row(cells)?;
+        let world = self.parent_world().then_apply(&local);
+        self.open_groups.push(MiscGroup { id, name, local, world, line, children: vec![] });
+        Ok(())
+    }
+
+    /// Returns `false` if there was no group to close
+    pub(crate) fn close_group(&mut self) -> bool {
+        let Some(group) = self.open_groups.pop() else {
+            return false;
+        };
+        self.push_node(MiscNode::Group(group));
+        true
+    }
+
+    /// Closes all groups missing an "EndMiscGroup" row and returns them
+    pub(crate) fn finish(&mut self) -> Vec<(i32, usize)> {
+        let mut unclosed = vec![];
+        while let Some(group) = self.open_groups.last() {
+            unclosed.push((group.id, group.line));
+            self.close_group();
+        }
+        unclosed
+    }
+}
+
+// Misc;id;prefab;x;y;z;rot x;rot y;rot z
+// MiscGroup;id;name;x;y;z;rot x;rot y;rot z
+fn parse_misc_row(cells: &[&str]) -> anyhow::Result<(i32, String, Transform)> {
+    ensure!(cells.len() >= 9);
+    let id = cells[1].parse()?;
+    let transform = Transform::new(parse_position(&cells[3..6])?, parse_transform(&cells[6..9])?);
+    Ok((id, cells[2].to_string(), transform))
+}
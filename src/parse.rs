@@ -1,20 +1,33 @@
-use crate::math::{RotatedCircle, Vec3Ext};
-use crate::track_structures::{Crossing, ForkSwitch, SlipSwitch, TrackStructure, TRACK_STRUCTURES};
+use crate::camera::{parse_camera, Camera, Viewport};
+use crate::components::check_components;
+use crate::diagnostics::{Diagnostic, DiagnosticKind, Severity};
+use crate::math::{rotation_from_direction, RotatedCircle};
+use crate::misc::MiscTree;
+use crate::sampling::{cubic_bezier, ArcLengthTable};
+use crate::scenery_file::{read_rows, write_rows, SceneryRow};
+use crate::topology::{validate_links, LinkProblem};
+use crate::terrain::{parse_terrain_point, Terrain, TerrainGroup};
+use crate::track_graph::TrackGraph;
+use crate::track_objects::{parse_track_object, TrackObject};
+use crate::transform::Transform;
+use crate::wires::{match_wires, parse_wires, CatenarySpan};
+use crate::track_structures::{
+    Crossing, ForkSwitch, GameVersion, SlipSwitch, TrackStructure, TrackStructureCatalogue, TrackStructureCatalogues,
+};
 use anyhow::{bail, ensure};
-use bezier_nd::Bezier;
-use glam::{Mat3, Vec3, Vec3Swizzles};
+use glam::{Mat3, Vec2, Vec3, Vec3Swizzles};
 use lazy_regex::regex_captures;
 use std::collections::HashMap;
 use std::f32::consts::PI;
-use std::io::{BufRead, BufReader, Read};
-use std::mem::swap;
+use std::io::{Read, Write};
 
 static BEZIER_STRAIGHTNESS: f32 = 0.01;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 enum State {
     Default,
-    Route,
+    /// Inside a route block, with the index of the route in `ParseResult::routes`
+    Route(usize),
     TerrainGroup,
 }
 
@@ -35,6 +48,22 @@ impl Checkpoint {
     fn rotate(self, angle: f32) -> Self {
         Checkpoint { pos: self.pos, rotation: self.rotation * Mat3::from_rotation_y(angle) }
     }
+
+    fn transformed(self, transform: &Transform) -> Self {
+        Checkpoint {
+            pos: transform.apply_to_pos(self.pos),
+            rotation: transform.apply_to_rotation(self.rotation),
+        }
+    }
+}
+
+/// Rotation at an end of a bezier, facing along the first usable tangent.
+/// The later tangents are fallbacks for control points placed on the end point.
+fn bezier_end_rotation(tangents: [Vec3; 3]) -> Mat3 {
+    tangents
+        .into_iter()
+        .find_map(rotation_from_direction)
+        .unwrap_or(Mat3::IDENTITY)
 }
 
 #[derive(Debug, Clone)]
@@ -56,7 +85,7 @@ pub(crate) enum TrackShape {
         control1: Vec3,
         control2: Vec3,
         end_pos: Vec3,
-        length: f32,
+        arc_lengths: ArcLengthTable,
     },
     Point(Checkpoint),
 }
@@ -75,7 +104,7 @@ impl TrackShape {
         TrackShape::Straight {
             start: Checkpoint { pos: start_pos, rotation: point.rotation },
             end_pos,
-            length: start_offset,
+            length,
         }
     }
 
@@ -113,21 +142,15 @@ impl TrackShape {
         control2: Vec3,
         end_pos: Vec3,
     ) -> Self {
-        let bezier = Bezier::cubic(
-            &start_pos.to_geo_nd(),
-            &control1.to_geo_nd(),
-            &control2.to_geo_nd(),
-            &end_pos.to_geo_nd(),
-        );
-
-        let length = bezier.length(BEZIER_STRAIGHTNESS);
+        let bezier = cubic_bezier(start_pos, control1, control2, end_pos);
+        let arc_lengths = ArcLengthTable::new(&bezier, BEZIER_STRAIGHTNESS);
 
         TrackShape::Bezier {
             start_pos,
             control1,
             control2,
             end_pos,
-            length,
+            arc_lengths,
         }
     }
 
@@ -141,9 +164,9 @@ impl TrackShape {
             TrackShape::Arc { start_pos, rotated_circle, .. } => {
                 Checkpoint { pos: *start_pos, rotation: rotated_circle.start_rotation() }
             },
-            TrackShape::Bezier { start_pos: start, .. } => {
-                // TODO
-                Checkpoint { pos: *start, rotation: Mat3::IDENTITY }
+            TrackShape::Bezier { start_pos, control1, control2, end_pos, .. } => {
+                let tangents = [*control1 - *start_pos, *control2 - *start_pos, *end_pos - *start_pos];
+                Checkpoint { pos: *start_pos, rotation: bezier_end_rotation(tangents) }
             },
             TrackShape::Point(point) => *point,
         }
@@ -155,14 +178,44 @@ impl TrackShape {
             TrackShape::Arc { start_pos, rotated_circle, angle, .. } => {
                 rotated_circle.move_by_angle(*start_pos, *angle)
             },
-            TrackShape::Bezier { end_pos, .. } => {
-                // TODO
-                Checkpoint { pos: *end_pos, rotation: Mat3::IDENTITY }
+            TrackShape::Bezier { start_pos, control1, control2, end_pos, .. } => {
+                let tangents = [*end_pos - *control2, *end_pos - *control1, *end_pos - *start_pos];
+                Checkpoint { pos: *end_pos, rotation: bezier_end_rotation(tangents) }
             }
             TrackShape::Point(point) => *point,
         }
     }
 
+    pub(crate) fn transformed(&self, transform: &Transform) -> Self {
+        match self {
+            TrackShape::Straight { start, end_pos, length } => TrackShape::Straight {
+                start: start.transformed(transform),
+                end_pos: transform.apply_to_pos(*end_pos),
+                length: *length,
+            },
+            TrackShape::Arc { start_pos, end, length, angle, rotated_circle } => TrackShape::Arc {
+                start_pos: transform.apply_to_pos(*start_pos),
+                end: end.transformed(transform),
+                length: *length,
+                angle: *angle,
+                rotated_circle: rotated_circle.rotated(transform.rotation),
+            },
+            TrackShape::Bezier { start_pos, control1, control2, end_pos, arc_lengths } => TrackShape::Bezier {
+                start_pos: transform.apply_to_pos(*start_pos),
+                control1: transform.apply_to_pos(*control1),
+                control2: transform.apply_to_pos(*control2),
+                end_pos: transform.apply_to_pos(*end_pos),
+                arc_lengths: arc_lengths.clone(),
+            },
+            TrackShape::Point(point) => TrackShape::Point(point.transformed(transform)),
+        }
+    }
+
+    /// Position halfway along the track
+    pub(crate) fn midpoint(&self) -> Vec3 {
+        self.sample(self.length() / 2.0).pos
+    }
+
     pub(crate) fn lowest_y(&self) -> f32 {
         self.start().pos.y.min(self.end().pos.y)
     }
@@ -175,6 +228,16 @@ pub(crate) enum NextIds {
     Two(i32, i32),
 }
 
+impl NextIds {
+    pub(crate) fn to_vec(self) -> Vec<i32> {
+        match self {
+            NextIds::None => vec![],
+            NextIds::One(id) => vec![id],
+            NextIds::Two(id1, id2) => vec![id1, id2],
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct TrackIds {
     pub own: i32,
@@ -218,20 +281,115 @@ impl TrackIds {
     }
 }
 
+/// Columns of "Track" and "BTrack" rows after the geometry and the connected track IDs.
+/// Invalid values are left empty instead of failing the whole track.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackAttributes {
+    pub track_type: String,
+    /// Maximum speed in km/h
+    pub max_speed: Option<u32>,
+    /// Electrification flag as set in the editor, see also `Track::electrified`
+    pub electrification: Option<bool>,
+    /// Name of the isolated section (track circuit) the track belongs to
+    pub isolation: String,
+    pub name: String,
+    /// Remaining columns, not interpreted
+    pub extra: Vec<String>,
+}
+
+fn parse_flag(cell: &str) -> Option<bool> {
+    match cell {
+        "1" | "true" | "True" => Some(true),
+        "0" | "false" | "False" => Some(false),
+        _ => None,
+    }
+}
+
+impl TrackAttributes {
+    // type;max speed;electrification;isolation;name;...
+    fn parse(cells: &[&str]) -> Self {
+        let cell = |index: usize| cells.get(index).copied().unwrap_or_default();
+        TrackAttributes {
+            track_type: cell(0).to_string(),
+            max_speed: cell(1).parse().ok(),
+            electrification: parse_flag(cell(2)),
+            isolation: cell(3).to_string(),
+            name: cell(4).to_string(),
+            extra: cells.iter().skip(5).map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Track {
     pub ids: TrackIds,
     pub(crate) shape: TrackShape,
     pub(crate) end_for_structure: Option<String>,
+    /// Set to the track kind if it was not recognised and the shape is only a placeholder
+    pub(crate) placeholder_for: Option<String>,
+    /// Part of a track structure, but not linked to anything, e.g. the unused centre of a single slip switch
+    pub(crate) unusable: bool,
+    /// Line of the scenery file the track (or its track structure) was defined on
+    pub line: Option<usize>,
+    /// Index in `ParseResult::routes` of the route block containing this track
+    pub route: Option<usize>,
+    /// Whether the track runs under the overhead wires along its whole length
+    pub electrified: bool,
+    /// Attributes from the row of a "Track" or "BTrack", empty for tracks of track structures
+    pub attributes: TrackAttributes,
 }
 
 impl Track {
     pub(crate) fn new(ids: TrackIds, shape: TrackShape) -> Self {
-        Track { ids, shape, end_for_structure: None }
+        Track {
+            ids,
+            shape,
+            end_for_structure: None,
+            placeholder_for: None,
+            unusable: false,
+            line: None,
+            route: None,
+            electrified: false,
+            attributes: TrackAttributes::default(),
+        }
     }
 
     pub(crate) fn new_structure_end(ids: TrackIds, shape: TrackShape, end_for_structure: String) -> Self {
-        Track { ids, shape, end_for_structure: Some(end_for_structure) }
+        Track { end_for_structure: Some(end_for_structure), ..Track::new(ids, shape) }
+    }
+
+    pub(crate) fn new_placeholder(ids: TrackIds, shape: TrackShape, placeholder_for: String) -> Self {
+        Track { placeholder_for: Some(placeholder_for), ..Track::new(ids, shape) }
+    }
+
+    pub(crate) fn new_unusable(ids: TrackIds, shape: TrackShape) -> Self {
+        Track { unusable: true, ..Track::new(ids, shape) }
+    }
+
+    pub(crate) fn with_attributes(mut self, attributes: TrackAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    pub fn is_placeholder(&self) -> bool {
+        self.placeholder_for.is_some()
+    }
+
+    pub fn is_unusable(&self) -> bool {
+        self.unusable
+    }
+
+    /// Tells whether the track with `other_id` is connected at the end (rather than the start) of this track.
+    /// Decided by the links of this track, or by the distance to `pos` if they link both ends or neither.
+    pub(crate) fn is_linked_at_end(&self, other_id: i32, pos: Vec3) -> bool {
+        let links_start = self.ids.prev == Some(other_id);
+        let links_end = self.ids.next.to_vec().contains(&other_id);
+        if links_start != links_end {
+            return links_end;
+        }
+        let start_distance = (self.shape.start().pos.xz() - pos.xz()).length_squared();
+        let end_distance = (self.shape.end().pos.xz() - pos.xz()).length_squared();
+        end_distance < start_distance
     }
 }
 
@@ -241,22 +399,163 @@ pub struct Switch {
     pub(crate) tracks: Vec<Track>,
 }
 
-#[derive(Debug)]
+/// What is wrong at a joint between two tracks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JointMismatch {
+    /// The ends are too far apart horizontally
+    Gap,
+    /// The ends are at different heights
+    HeightStep,
+    /// The tracks meet at an angle
+    Kink,
+}
+
+impl JointMismatch {
+    fn diagnostic_kind(self) -> DiagnosticKind {
+        match self {
+            JointMismatch::Gap => DiagnosticKind::JointGap,
+            JointMismatch::HeightStep => DiagnosticKind::JointHeightStep,
+            JointMismatch::Kink => DiagnosticKind::JointKink,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct FailedConnection {
     pub pos1: Vec3,
     pub pos2: Vec3,
     pub track1: Track,
     pub track2: Track,
+    pub mismatch: JointMismatch,
 }
 
-#[derive(Debug)]
+/// Largest mismatches allowed where two tracks meet
+#[derive(Debug, Copy, Clone)]
+pub struct JointTolerances {
+    /// Horizontal distance between the ends
+    pub max_gap: f32,
+    /// Vertical distance between the ends
+    pub max_height_step: f32,
+    /// Angle between the headings of the tracks in the XZ plane, in radians
+    pub max_heading_difference: f32,
+}
+
+impl Default for JointTolerances {
+    fn default() -> Self {
+        JointTolerances {
+            max_gap: 1.0,
+            max_height_step: 0.1,
+            max_heading_difference: 1f32.to_radians(),
+        }
+    }
+}
+
+/// Options for `parse_with_options`
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub joint_tolerances: JointTolerances,
+    /// Geometry of the track structures for every game version, the built-in ones by default
+    pub catalogues: TrackStructureCatalogues,
+    /// Game version the scenery was saved in. If not set, it is guessed from the track structures used,
+    /// picking the newest version with all of them, so it must be set for sceneries saved in older versions.
+    pub game_version: Option<GameVersion>,
+}
+
+/// A block of rows between "Route" and "EndRoute", describing a line leading out of the station
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub name: String,
+    /// Remaining cells of the "Route" row, after the name
+    pub attributes: Vec<String>,
+    /// Line of the "Route" row
+    pub line: usize,
+    /// IDs of the tracks defined inside the route block, in file order
+    pub track_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone)]
 pub struct ParseResult {
     pub tracks: Vec<Track>,
+    pub routes: Vec<Route>,
+    pub terrain: Terrain,
+    pub track_objects: Vec<TrackObject>,
+    pub misc: MiscTree,
+    pub catenary: Vec<CatenarySpan>,
+    /// Placement of the scenery in the world, from the "WorldRotation" and "WorldTranslation" rows.
+    /// All other geometry is in the local frame of the scenery, unless converted with `to_world_frame`.
+    pub world_transform: Transform,
+    pub main_camera: Option<Camera>,
+    pub camera_home: Option<Camera>,
     pub track_indexes: HashMap<i32, usize>,
     pub failed_connections: Vec<FailedConnection>,
+    pub link_problems: Vec<LinkProblem>,
+    /// Game version of the track structure catalogue used
+    pub game_version: Option<GameVersion>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// All rows of the file, including the ones not understood by the parser
+    pub rows: Vec<SceneryRow>,
+}
+
+impl ParseResult {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|x| x.severity == Severity::Error)
+    }
+
+    /// Writes the scenery back in the `.sc` format.
+    /// If no rows were modified, the output is identical to the parsed file.
+    pub fn write<W: Write>(&self, writer: W) -> anyhow::Result<()> {
+        write_rows(&self.rows, writer)
+    }
+
+    /// Returns the row from the given line of the original file
+    pub fn row_mut(&mut self, line: usize) -> Option<&mut SceneryRow> {
+        self.rows.iter_mut().find(|x| x.line == Some(line))
+    }
+
+    /// Returns a copy with all geometry converted to world coordinates,
+    /// so that neighbouring sceneries line up. The `world_transform` of the copy is the identity.
+    pub fn to_world_frame(&self) -> ParseResult {
+        let transform = self.world_transform;
+        let mut result = self.clone();
+        for track in &mut result.tracks {
+            track.shape = track.shape.transformed(&transform);
+        }
+        for failed_connection in &mut result.failed_connections {
+            failed_connection.pos1 = transform.apply_to_pos(failed_connection.pos1);
+            failed_connection.pos2 = transform.apply_to_pos(failed_connection.pos2);
+            failed_connection.track1.shape = failed_connection.track1.shape.transformed(&transform);
+            failed_connection.track2.shape = failed_connection.track2.shape.transformed(&transform);
+        }
+        for link_problem in &mut result.link_problems {
+            link_problem.transform(&transform);
+        }
+        result.terrain.transform(&transform);
+        for track_object in &mut result.track_objects {
+            track_object.transform(&transform);
+        }
+        result.misc.transform(&transform);
+        for span in &mut result.catenary {
+            span.transform(&transform);
+        }
+        for camera in [&mut result.main_camera, &mut result.camera_home].into_iter().flatten() {
+            camera.transform(&transform);
+        }
+        result.world_transform = Transform::IDENTITY;
+        result
+    }
+
+    /// Area seen from the home camera position (or the main camera if there is no home position),
+    /// with the ground height taken from the terrain under the camera.
+    pub fn home_viewport(&self) -> Option<Viewport> {
+        let camera = self.camera_home.or(self.main_camera)?;
+        let ground_y = self.terrain.height_at(camera.pos.x, camera.pos.z).unwrap_or_else(|| {
+            self.tracks.iter().map(|x| x.shape.lowest_y()).fold(f32::INFINITY, f32::min).min(camera.pos.y)
+        });
+        Some(camera.viewport(ground_y))
+    }
 }
 
-fn parse_position(cells: &[&str]) -> anyhow::Result<Vec3> {
+pub(crate) fn parse_position(cells: &[&str]) -> anyhow::Result<Vec3> {
     ensure!(cells.len() == 3);
     Ok(Vec3 {
         x: cells[0].parse()?,
@@ -265,7 +564,7 @@ fn parse_position(cells: &[&str]) -> anyhow::Result<Vec3> {
     })
 }
 
-fn parse_transform(cells: &[&str]) -> anyhow::Result<Mat3> {
+pub(crate) fn parse_transform(cells: &[&str]) -> anyhow::Result<Mat3> {
     ensure!(cells.len() == 3);
     let x_deg: f32 = cells[0].parse()?;
     let y_deg: f32 = cells[1].parse()?;
@@ -288,7 +587,7 @@ fn parse_normal_track(cells: &[&str]) -> anyhow::Result<Track> {
     let radius: f32 = cells[10].parse()?;
 
     let shape = TrackShape::arc_or_straight(start, radius, length);
-    Ok(Track::new(ids, shape))
+    Ok(Track::new(ids, shape).with_attributes(TrackAttributes::parse(&cells[13..])))
 }
 
 fn parse_bezier_track(cells: &[&str]) -> anyhow::Result<Track> {
@@ -296,19 +595,27 @@ fn parse_bezier_track(cells: &[&str]) -> anyhow::Result<Track> {
 
     let ids = TrackIds::parse(cells[1], cells[16], cells[15])?;
 
+    // Both control points are offsets in the scenery frame,
+    // the first one from the start and the second one from the end
     let start_pos = parse_position(&cells[3..6])?;
     let start_to_control1 = parse_position(&cells[6..9])?;
-    let start_to_end = parse_position(&cells[9..12])? - start_pos;
+    let end_pos = parse_position(&cells[9..12])?;
     let end_to_control2 = parse_position(&cells[12..15])?;
-    let start_to_control2 = start_to_end + end_to_control2;
-    let rotation = Mat3::IDENTITY;
 
-    let control1 = start_pos + rotation * start_to_control1;
-    let control2 = start_pos + rotation * start_to_control2;
-    let end_pos = start_pos + rotation * start_to_end;
+    let control1 = start_pos + start_to_control1;
+    let control2 = end_pos + end_to_control2;
 
     let shape = TrackShape::bezier(start_pos, control1, control2, end_pos);
-    Ok(Track::new(ids, shape))
+    Ok(Track::new(ids, shape).with_attributes(TrackAttributes::parse(&cells[17..])))
+}
+
+/// Builds a point-shaped stand-in for a track of an unknown kind.
+/// Only the ID and start position are read, as the column layout of other kinds is not known.
+fn parse_placeholder_track(cells: &[&str], kind: &str) -> anyhow::Result<Track> {
+    ensure!(cells.len() >= 6);
+    let ids = TrackIds::parse(cells[1], "", "")?;
+    let start = Checkpoint::new(parse_position(&cells[3..6])?, Mat3::IDENTITY);
+    Ok(Track::new_placeholder(ids, TrackShape::point(start), kind.to_string()))
 }
 
 // https://wiki.td2.info.pl/index.php?title=Scenery_format
@@ -317,7 +624,7 @@ fn parse_track(cells: &[&str]) -> anyhow::Result<Track> {
     Ok(match cells[2] {
         "Track" => parse_normal_track(cells)?,
         "BTrack" => parse_bezier_track(cells)?,
-        &_ => panic!(),
+        kind => parse_placeholder_track(cells, kind)?,
     })
 }
 
@@ -514,10 +821,8 @@ fn build_slip_switch(
             ]
         } else {
             // Don't add prev or next, this track is not usable
-            let center_ids = center_ids;
-
             vec![
-                Track::new(center_ids, TrackShape::straight_between(enter_path[1].shape.end().pos, exit_path[3].shape.end().pos, start.rotation)),
+                Track::new_unusable(center_ids, TrackShape::straight_between(enter_path[1].shape.end().pos, exit_path[3].shape.end().pos, start.rotation)),
             ]
         }
     };
@@ -570,7 +875,7 @@ fn parse_subtrack_ids(cell: &str) -> anyhow::Result<Vec<TrackIds>> {
     Ok(ids)
 }
 
-fn parse_track_structure(cells: &[&str]) -> anyhow::Result<Switch> {
+fn parse_track_structure(cells: &[&str], catalogue: &TrackStructureCatalogue) -> anyhow::Result<Switch> {
     ensure!(cells.len() >= 19);
     let id = cells[1].parse()?;
     let start = Checkpoint {
@@ -584,7 +889,7 @@ fn parse_track_structure(cells: &[&str]) -> anyhow::Result<Switch> {
 
     let subtracks = parse_subtrack_ids(cells[9])?;
 
-    let tracks: Vec<Track> = if let Some(track_structure) = TRACK_STRUCTURES.get(structure_name) {
+    let tracks: Vec<Track> = if let Some(track_structure) = catalogue.get(structure_name) {
         match track_structure {
             TrackStructure::Fork(fork) => build_fork_switch(start, fork, subtracks, structure_name)?,
             TrackStructure::Slip(slip) => build_slip_switch(start, slip, subtracks, structure_name)?,
@@ -600,121 +905,433 @@ fn parse_track_structure(cells: &[&str]) -> anyhow::Result<Switch> {
     })
 }
 
-fn find_failed_connections(tracks: &Vec<Track>, track_indexes: &HashMap<i32, usize>) -> Vec<FailedConnection> {
+/// Direction pointing away from the track at the given end, flattened to the XZ plane
+fn outward_heading(shape: &TrackShape, at_end: bool) -> Vec2 {
+    if at_end {
+        (shape.end().rotation * Vec3::Z).xz()
+    } else {
+        -(shape.start().rotation * Vec3::Z).xz()
+    }
+}
+
+fn find_failed_connections(
+    tracks: &[Track],
+    track_indexes: &HashMap<i32, usize>,
+    tolerances: &JointTolerances,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<FailedConnection> {
     let mut failed_connections: Vec<FailedConnection> = vec![];
 
     for track in tracks {
-        let mut check_neighbour = |id: i32, pos: Vec3| {
-            if let Some(other_index) = track_indexes.get(&id) {
-                let other = &tracks[*other_index];
-                let mut dist_min = (other.shape.start().pos, (other.shape.start().pos.xz() - pos.xz()).length_squared()) ;
-                let mut dist_max = (other.shape.end().pos, (other.shape.end().pos.xz() - pos.xz()).length_squared());
-                if dist_max.1 < dist_min.1 {
-                    swap(&mut dist_min, &mut dist_max);
-                }
-                let dist_squared = dist_min.1;
-                if dist_squared > 1.0 {
-                    failed_connections.push(FailedConnection {
-                        pos1: pos,
-                        pos2: dist_min.0,
-                        track1: track.clone(),
-                        track2: other.clone(),
-                    });
-                    let difference = dist_min.0 - pos;
-                    println!(
-                        "The tracks {} and {} don't align - their ends are at a distance of {}: (x: {}, y (ignored): {}, z: {})",
-                        track.ids.own,
-                        other.ids.own,
-                        dist_squared.sqrt(),
-                        difference.x,
-                        difference.y,
-                        difference.z,
-                    );
-                    if let Some(structure_name) = &track.end_for_structure {
-                        println!("Track {} is part of the track structure \"{}\"", track.ids.own, structure_name);
-                    }
+        let mut check_neighbour = |id: i32, at_end: bool| {
+            let Some(other_index) = track_indexes.get(&id) else {
+                return;
+            };
+            let other = &tracks[*other_index];
+            // The real geometry of placeholders is unknown, so their ends can't be compared
+            if track.is_placeholder() || other.is_placeholder() {
+                return;
+            }
+            let pos = if at_end { track.shape.end().pos } else { track.shape.start().pos };
+            let other_at_end = other.is_linked_at_end(track.ids.own, pos);
+            let other_pos = if other_at_end { other.shape.end().pos } else { other.shape.start().pos };
+            let difference = other_pos - pos;
+            let distance = difference.xz().length();
+
+            // The checks are independent, so a joint can fail several of them
+            let mut mismatches: Vec<(JointMismatch, String)> = vec![];
+            if distance > tolerances.max_gap {
+                let message = format!(
+                    "The tracks {} and {} don't align - their ends are at a distance of {}: (x: {}, y (ignored): {}, z: {})",
+                    track.ids.own,
+                    other.ids.own,
+                    distance,
+                    difference.x,
+                    difference.y,
+                    difference.z,
+                );
+                mismatches.push((JointMismatch::Gap, message));
+            }
+            if difference.y.abs() > tolerances.max_height_step {
+                let message = format!(
+                    "The tracks {} and {} meet at different heights - the difference is {}",
+                    track.ids.own,
+                    other.ids.own,
+                    difference.y,
+                );
+                mismatches.push((JointMismatch::HeightStep, message));
+            }
+            // Smoothly joined tracks point away from the joint in opposite directions
+            let heading = outward_heading(&track.shape, at_end);
+            let other_heading = -outward_heading(&other.shape, other_at_end);
+            let angle = heading.angle_to(other_heading).abs();
+            if heading.length_squared() > 0.0 && other_heading.length_squared() > 0.0 && angle > tolerances.max_heading_difference {
+                let message = format!(
+                    "The tracks {} and {} meet at an angle of {} degrees",
+                    track.ids.own,
+                    other.ids.own,
+                    angle.to_degrees(),
+                );
+                mismatches.push((JointMismatch::Kink, message));
+            }
+
+            for (mismatch, mut message) in mismatches {
+                failed_connections.push(FailedConnection {
+                    pos1: pos,
+                    pos2: other_pos,
+                    track1: track.clone(),
+                    track2: other.clone(),
+                    mismatch,
+                });
+                if let Some(structure_name) = &track.end_for_structure {
+                    message += &format!(". Track {} is part of the track structure \"{}\"", track.ids.own, structure_name);
                 }
+                diagnostics.push(
+                    Diagnostic::error(message)
+                        .at_line(track.line)
+                        .with_id(Some(track.ids.own))
+                        .with_kind(mismatch.diagnostic_kind()),
+                );
             }
         };
         if let Some(prev_id) = track.ids.prev {
-            check_neighbour(prev_id, track.shape.start().pos);
+            check_neighbour(prev_id, false);
         }
-        match track.ids.next {
-            NextIds::None => {},
-            NextIds::One(id) => check_neighbour(id, track.shape.end().pos),
-            NextIds::Two(id1, id2) => {
-                check_neighbour(id1, track.shape.end().pos);
-                check_neighbour(id2, track.shape.end().pos);
-            },
+        for id in track.ids.next.to_vec() {
+            check_neighbour(id, true);
         }
     }
 
     failed_connections
 }
 
+/// Parses the ID from the second cell of a row, if present
+fn row_id(cells: &[&str]) -> Option<i32> {
+    cells.get(1).and_then(|x| x.parse().ok())
+}
+
+/// Parses a "Track" or "TrackStructure" row into the tracks it defines.
+/// Problems are reported to `diagnostics` and result in no tracks.
+fn parse_track_row(
+    cells: &[&str],
+    line_number: usize,
+    catalogue: &TrackStructureCatalogue,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Track> {
+    let row_kind = cells[0];
+    let parsed = match row_kind {
+        "Track" => parse_track(cells).map(|track| vec![track]),
+        _ => parse_track_structure(cells, catalogue).map(|switch| switch.tracks),
+    };
+    match parsed {
+        Ok(mut tracks) => {
+            for track in &mut tracks {
+                track.line = Some(line_number);
+                if let Some(kind) = &track.placeholder_for {
+                    diagnostics.push(
+                        Diagnostic::error(format!("Unknown track kind {kind}, using a placeholder"))
+                            .at_line(Some(line_number))
+                            .with_row_kind(row_kind)
+                            .with_id(Some(track.ids.own)),
+                    );
+                }
+            }
+            tracks
+        },
+        Err(e) => {
+            let message = match row_kind {
+                "Track" => format!("Failed to parse track: {e}"),
+                _ => format!("Failed to parse switch: {e}"),
+            };
+            diagnostics.push(
+                Diagnostic::error(message)
+                    .at_line(Some(line_number))
+                    .with_row_kind(row_kind)
+                    .with_id(row_id(cells)),
+            );
+            vec![]
+        },
+    }
+}
+
+fn add_terrain_point(
+    terrain: &mut Terrain,
+    cells: &[&str],
+    line_number: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<usize> {
+    match parse_terrain_point(cells) {
+        Ok(point) => {
+            terrain.points.push(point);
+            Some(terrain.points.len() - 1)
+        },
+        Err(e) => {
+            diagnostics.push(
+                Diagnostic::warning(format!("Failed to parse terrain point: {e}"))
+                    .at_line(Some(line_number))
+                    .with_row_kind(cells[0]),
+            );
+            None
+        },
+    }
+}
+
+fn add_track_object(
+    track_objects: &mut Vec<TrackObject>,
+    cells: &[&str],
+    line_number: usize,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match parse_track_object(cells, line_number) {
+        Ok(track_object) => track_objects.push(track_object),
+        Err(e) => diagnostics.push(
+            Diagnostic::warning(format!("Failed to parse track object: {e}"))
+                .at_line(Some(line_number))
+                .with_row_kind(cells[0])
+                .with_id(row_id(cells)),
+        ),
+    }
+}
+
+fn parse_route_header(cells: &[&str], line_number: usize) -> Route {
+    Route {
+        name: cells.get(1).copied().unwrap_or_default().to_string(),
+        attributes: cells.iter().skip(2).map(|x| x.to_string()).collect(),
+        line: line_number,
+        track_ids: vec![],
+    }
+}
+
 pub fn parse<R: Read>(input: R) -> anyhow::Result<ParseResult> {
-    let lines = BufReader::new(input).lines();
+    parse_with_options(input, &ParseOptions::default())
+}
+
+pub fn parse_with_options<R: Read>(input: R, options: &ParseOptions) -> anyhow::Result<ParseResult> {
+    let rows = read_rows(input)?;
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    let catalogue = match &options.game_version {
+        Some(version) => options.catalogues.for_version(version),
+        None => {
+            let structure_names: Vec<&str> = rows
+                .iter()
+                .filter(|x| x.kind() == "TrackStructure")
+                .filter_map(|x| x.cells.get(2)?.split(',').next())
+                .collect();
+            let candidates = options.catalogues.matching(structure_names.iter().copied());
+            let catalogue = options.catalogues.detect(structure_names.iter().copied());
+            // Sceneries without track structures read the same with all the catalogues
+            if candidates.len() > 1 && !structure_names.is_empty() {
+                let version = catalogue.game_version.as_ref().map(|x| x.to_string()).unwrap_or_default();
+                diagnostics.push(Diagnostic::warning(format!(
+                    "The game version was guessed as {version}, the newest of {} versions with all the track structures used. \
+                    Set it in the parse options if the scenery was saved in an older version",
+                    candidates.len(),
+                )));
+            }
+            catalogue
+        },
+    };
 
     let mut tracks: Vec<Track> = vec![];
+    let mut routes: Vec<Route> = vec![];
+    let mut terrain = Terrain::default();
+    let mut track_objects: Vec<TrackObject> = vec![];
+    let mut misc = MiscTree::default();
+    let mut catenary: Vec<CatenarySpan> = vec![];
+    let mut world_transform = Transform::IDENTITY;
+    let mut main_camera: Option<Camera> = None;
+    let mut camera_home: Option<Camera> = None;
 
     let mut state = State::Default;
-    lines.flatten().for_each(|line| {
-        if line.is_empty() {
-            return;
+    for (index, row) in rows.iter().enumerate() {
+        let line_number = index + 1;
+        if row.cells.len() == 1 && row.cells[0].is_empty() {
+            continue;
+        }
+        if !row.is_valid_utf8() {
+            diagnostics.push(
+                Diagnostic::warning("The line is not valid UTF-8, the invalid bytes were replaced")
+                    .at_line(Some(line_number)),
+            );
         }
 
-        let cells: Vec<&str> = line.split(";").collect();
+        let cells: Vec<&str> = row.cells.iter().map(String::as_str).collect();
         let row_kind = cells[0];
 
-        match state {
-            State::Default => match row_kind {
-                "Route" => {
-                    state = State::Route;
+        // Rows specific to the current block come first, the remaining ones are handled the same in every block
+        match (state, row_kind) {
+            (State::Default, "Route") => {
+                routes.push(parse_route_header(&cells, line_number));
+                state = State::Route(routes.len() - 1);
+            }
+            (State::Default, "TerrainGroup") => {
+                terrain.groups.push(TerrainGroup { line: line_number, point_indexes: vec![] });
+                state = State::TerrainGroup;
+            }
+            (State::Route(_), "EndRoute") | (State::TerrainGroup, "EndTerrainGroup") => {
+                state = State::Default;
+            }
+            (State::Route(route_index), "Track" | "TrackStructure") => {
+                for mut track in parse_track_row(&cells, line_number, catalogue, &mut diagnostics) {
+                    track.route = Some(route_index);
+                    routes[route_index].track_ids.push(track.ids.own);
+                    tracks.push(track);
                 }
-                "TerrainGroup" => {
-                    state = State::TerrainGroup;
+            }
+            (State::TerrainGroup, "TerrainPoint") => {
+                if let Some(index) = add_terrain_point(&mut terrain, &cells, line_number, &mut diagnostics) {
+                    if let Some(group) = terrain.groups.last_mut() {
+                        group.point_indexes.push(index);
+                    }
                 }
-                "Track" => match parse_track(&cells) {
-                    Ok(track) => {
-                        tracks.push(track);
-                    },
-                    Err(e) => println!("Failed to parse track: {e}"),
-                },
-                "TrackStructure" => match parse_track_structure(&cells) {
-                    Ok(switch) => {
-                        tracks.extend(switch.tracks);
-                    },
-                    Err(e) => println!("Failed to parse switch: {e}"),
-                },
-                "TrackObject" | "Misc" | "Fence" | "Wires" | "TerrainPoint" | "MiscGroup"
-                | "EndMiscGroup" | "SSPController" | "SSPRepeater" | "scv029" | "shv001"
-                | "WorldRotation" | "WorldTranslation" | "MainCamera" | "CameraHome" => {},
-                extra => {
-                    println!("Unknown kind: {extra}")
+            }
+            (_, "Route" | "EndRoute" | "TerrainGroup" | "EndTerrainGroup") => diagnostics.push(
+                Diagnostic::warning(format!("Unexpected {row_kind}"))
+                    .at_line(Some(line_number))
+                    .with_row_kind(row_kind),
+            ),
+            (_, "TerrainPoint") => {
+                add_terrain_point(&mut terrain, &cells, line_number, &mut diagnostics);
+            }
+            (_, "TrackObject") => {
+                add_track_object(&mut track_objects, &cells, line_number, &mut diagnostics);
+            }
+            (_, "Misc" | "MiscGroup") => {
+                let result = if row_kind == "Misc" {
+                    misc.add_object(&cells, line_number)
+                } else {
+                    misc.open_group(&cells, line_number)
+                };
+                if let Err(e) = result {
+                    diagnostics.push(
+                        Diagnostic::warning(format!("Failed to parse {row_kind}: {e}"))
+                            .at_line(Some(line_number))
+                            .with_row_kind(row_kind)
+                            .with_id(row_id(&cells)),
+                    );
                 }
-            },
-            State::Route => {
-                if row_kind == "EndRoute" {
-                    state = State::Default;
+            }
+            (_, "EndMiscGroup") => {
+                if !misc.close_group() {
+                    diagnostics.push(
+                        Diagnostic::warning("EndMiscGroup without a matching MiscGroup")
+                            .at_line(Some(line_number))
+                            .with_row_kind(row_kind),
+                    );
                 }
-            },
-            State::TerrainGroup => {
-                if row_kind == "EndTerrainGroup" {
-                    state = State::Default;
+            }
+            (_, "WorldRotation" | "WorldTranslation") => {
+                let result = if cells.len() < 4 {
+                    Err(anyhow::anyhow!("Expected 3 values"))
+                } else if row_kind == "WorldRotation" {
+                    parse_transform(&cells[1..4]).map(|x| world_transform.rotation = x)
+                } else {
+                    parse_position(&cells[1..4]).map(|x| world_transform.translation = x)
+                };
+                if let Err(e) = result {
+                    diagnostics.push(
+                        Diagnostic::warning(format!("Failed to parse {row_kind}: {e}"))
+                            .at_line(Some(line_number))
+                            .with_row_kind(row_kind),
+                    );
                 }
             }
+            (_, "MainCamera" | "CameraHome") => match parse_camera(&cells) {
+                Ok(camera) if row_kind == "MainCamera" => main_camera = Some(camera),
+                Ok(camera) => camera_home = Some(camera),
+                Err(e) => diagnostics.push(
+                    Diagnostic::warning(format!("Failed to parse {row_kind}: {e}"))
+                        .at_line(Some(line_number))
+                        .with_row_kind(row_kind),
+                ),
+            },
+            (_, "Wires") => match parse_wires(&cells, line_number) {
+                Ok(span) => catenary.push(span),
+                Err(e) => diagnostics.push(
+                    Diagnostic::warning(format!("Failed to parse wires: {e}"))
+                        .at_line(Some(line_number))
+                        .with_row_kind(row_kind)
+                        .with_id(row_id(&cells)),
+                ),
+            },
+            (_, "Track" | "TrackStructure") => {
+                tracks.extend(parse_track_row(&cells, line_number, catalogue, &mut diagnostics));
+            },
+            (_, "Fence" | "SSPController" | "SSPRepeater" | "scv029" | "shv001") => {},
+            (_, extra) => diagnostics.push(
+                Diagnostic::warning(format!("Unknown kind: {extra}"))
+                    .at_line(Some(line_number))
+                    .with_row_kind(extra),
+            ),
         }
-    });
+    }
+
+    if let State::Route(route_index) = state {
+        let route = &routes[route_index];
+        diagnostics.push(
+            Diagnostic::warning(format!("Route \"{}\" is missing EndRoute", route.name))
+                .at_line(Some(route.line))
+                .with_row_kind("Route"),
+        );
+    }
+
+    match_wires(&mut catenary, &mut tracks);
+
+    for (id, line) in misc.finish() {
+        diagnostics.push(
+            Diagnostic::warning(format!("MiscGroup {id} is missing EndMiscGroup"))
+                .at_line(Some(line))
+                .with_row_kind("MiscGroup")
+                .with_id(Some(id)),
+        );
+    }
 
     let mut track_indexes: HashMap<i32, usize> = HashMap::new();
     for (index, track) in tracks.iter().enumerate() {
         let prev = track_indexes.insert(track.ids.own, index);
         if prev.is_some() {
-            println!("Duplicate track ID found: {}", track.ids.own);
+            diagnostics.push(
+                Diagnostic::error(format!("Duplicate track ID found: {}", track.ids.own))
+                    .at_line(track.line)
+                    .with_id(Some(track.ids.own)),
+            );
+        }
+    }
+
+    for track_object in &track_objects {
+        if let Some(track_id) = track_object.track_id {
+            if !track_indexes.contains_key(&track_id) {
+                diagnostics.push(
+                    Diagnostic::warning(format!("Track object {} refers to unknown track {track_id}", track_object.id))
+                        .at_line(Some(track_object.line))
+                        .with_row_kind("TrackObject")
+                        .with_id(Some(track_object.id)),
+                );
+            }
         }
     }
 
-    let failed_connections = find_failed_connections(&tracks, &track_indexes);
+    let failed_connections = find_failed_connections(&tracks, &track_indexes, &options.joint_tolerances, &mut diagnostics);
+    let graph = TrackGraph::new(&tracks, &track_indexes);
+    let link_problems = validate_links(&tracks, &track_indexes, &graph);
+    diagnostics.extend(link_problems.iter().map(|x| x.to_diagnostic()));
+    diagnostics.extend(check_components(&tracks, &graph));
 
-    Ok(ParseResult { tracks, track_indexes, failed_connections })
+    Ok(ParseResult {
+        tracks,
+        routes,
+        terrain,
+        track_objects,
+        misc,
+        catenary,
+        world_transform,
+        main_camera,
+        camera_home,
+        track_indexes,
+        failed_connections,
+        link_problems,
+        game_version: catalogue.game_version.clone(),
+        diagnostics,
+        rows,
+    })
 }
@@ -0,0 +1,288 @@
+use crate::parse::{JointMismatch, ParseResult, Track, TrackShape};
+use crate::scenery_file::{format_number, LineEnding, SceneryRow};
+use anyhow::{bail, Context};
+use glam::Vec3;
+use std::collections::HashSet;
+
+/// Largest distance of the neighbouring end from the axis of a straight, for which only the length is changed
+static MAX_AXIS_DISTANCE: f32 = 0.01;
+
+#[derive(Debug, Clone)]
+pub enum RepairAction {
+    /// Changes the length of a straight, so that it ends at the neighbouring track
+    SetLength { track_id: i32, length: f32 },
+    /// Shifts a straight which is not connected at the other end, so that its misaligned end is at `pos`.
+    /// The heading of the straight is kept.
+    MoveEnd { track_id: i32, at_end: bool, pos: Vec3 },
+    /// Adds a bezier track between the ends, linked to both tracks in place of the failed connection
+    InsertConnector {
+        track_id: i32,
+        prev_id: i32,
+        next_id: i32,
+        start_pos: Vec3,
+        control1: Vec3,
+        control2: Vec3,
+        end_pos: Vec3,
+    },
+}
+
+/// A proposed fix for a failed connection
+#[derive(Debug, Clone)]
+pub struct Repair {
+    /// Line of the row to change, or of the row after which the connector is inserted
+    pub line: usize,
+    pub action: RepairAction,
+}
+
+impl Repair {
+    pub fn description(&self) -> String {
+        match &self.action {
+            RepairAction::SetLength { track_id, length } => {
+                format!("Change the length of track {track_id} to {length}")
+            },
+            RepairAction::MoveEnd { track_id, at_end, pos } => {
+                let end = if *at_end { "end" } else { "start" };
+                format!("Shift track {track_id} so that its {end} is at ({}, {}, {})", pos.x, pos.y, pos.z)
+            },
+            RepairAction::InsertConnector { track_id, prev_id, next_id, .. } => {
+                format!("Insert the bezier track {track_id} between the tracks {prev_id} and {next_id}")
+            },
+        }
+    }
+}
+
+fn set_position(row: &mut SceneryRow, index: usize, pos: Vec3) {
+    for (offset, value) in pos.to_array().into_iter().enumerate() {
+        row.set_cell(index + offset, format_number(value));
+    }
+}
+
+/// Returns the row of a "Track" of the "Track" kind, if it is a straight without roll
+fn editable_straight<'a>(parse_result: &'a ParseResult, track: &Track) -> Option<&'a SceneryRow> {
+    if !matches!(track.shape, TrackShape::Straight { .. }) || track.end_for_structure.is_some() {
+        return None;
+    }
+    let row = parse_result.rows.iter().find(|x| x.line == track.line)?;
+    let is_track = row.kind() == "Track" && row.cells.get(2).is_some_and(|x| x == "Track");
+    let no_roll = row.cells.get(8).and_then(|x| x.parse::<f32>().ok()) == Some(0.0);
+    (is_track && no_roll).then_some(row)
+}
+
+/// Tries to fix the joint by changing only the straight `track`, with `at_end` telling which end of it is misaligned
+fn repair_straight(parse_result: &ParseResult, track: &Track, at_end: bool, target: Vec3) -> Option<Repair> {
+    let row = editable_straight(parse_result, track)?;
+    let line = row.line?;
+    let TrackShape::Straight { start, .. } = &track.shape else {
+        return None;
+    };
+
+    if at_end {
+        let direction = start.rotation * Vec3::Z;
+        let offset = target - start.pos;
+        let length = offset.dot(direction);
+        if length > 0.0 && (offset - direction * length).length() <= MAX_AXIS_DISTANCE {
+            return Some(Repair { line, action: RepairAction::SetLength { track_id: track.ids.own, length } });
+        }
+    }
+
+    let free_standing = if at_end { track.ids.prev.is_none() } else { track.ids.next.to_vec().is_empty() };
+    if free_standing {
+        return Some(Repair { line, action: RepairAction::MoveEnd { track_id: track.ids.own, at_end, pos: target } });
+    }
+    None
+}
+
+/// Direction pointing away from the track at the end connected to `other_id`
+fn outward_direction(track: &Track, other_id: i32, pos: Vec3) -> Vec3 {
+    if track.is_linked_at_end(other_id, pos) {
+        track.shape.end().rotation * Vec3::Z
+    } else {
+        -(track.shape.start().rotation * Vec3::Z)
+    }
+}
+
+/// Proposes a fix for every gap and height step between tracks. Kinks and joints inside track structures are not repaired.
+/// The parse result must be in the local frame of the scenery, as the fixes are applied to its rows.
+pub fn propose_repairs(parse_result: &ParseResult) -> Vec<Repair> {
+    let mut repairs = vec![];
+    let mut repaired_pairs: HashSet<(i32, i32)> = HashSet::new();
+    let mut changed_tracks: HashSet<i32> = HashSet::new();
+    let mut next_free_id = parse_result.tracks.iter().map(|x| x.ids.own).max().unwrap_or(0) + 1;
+
+    for failed_connection in &parse_result.failed_connections {
+        if failed_connection.mismatch == JointMismatch::Kink {
+            continue;
+        }
+        let track1 = &failed_connection.track1;
+        let track2 = &failed_connection.track2;
+        // Both tracks come from the same "TrackStructure" row, whose geometry is fixed by the prefab
+        if track1.line.is_some() && track1.line == track2.line {
+            continue;
+        }
+        let pair = (track1.ids.own.min(track2.ids.own), track1.ids.own.max(track2.ids.own));
+        if !repaired_pairs.insert(pair) {
+            continue;
+        }
+
+        let ends = [
+            (track1, failed_connection.pos1, failed_connection.pos2),
+            (track2, failed_connection.pos2, failed_connection.pos1),
+        ];
+        let straight_repair = ends.iter().find_map(|(track, pos, target)| {
+            if changed_tracks.contains(&track.ids.own) {
+                return None;
+            }
+            let other_id = if track.ids.own == track1.ids.own { track2.ids.own } else { track1.ids.own };
+            repair_straight(parse_result, track, track.is_linked_at_end(other_id, *pos), *target)
+        });
+        if let Some(repair) = straight_repair {
+            if let RepairAction::SetLength { track_id, .. } | RepairAction::MoveEnd { track_id, .. } = repair.action {
+                changed_tracks.insert(track_id);
+            }
+            repairs.push(repair);
+            continue;
+        }
+
+        let Some(line) = track1.line else {
+            continue;
+        };
+        let start_pos = failed_connection.pos1;
+        let end_pos = failed_connection.pos2;
+        let handle_length = start_pos.distance(end_pos) / 3.0;
+        repairs.push(Repair {
+            line,
+            action: RepairAction::InsertConnector {
+                track_id: next_free_id,
+                prev_id: track1.ids.own,
+                next_id: track2.ids.own,
+                start_pos,
+                control1: start_pos + outward_direction(track1, track2.ids.own, start_pos) * handle_length,
+                control2: end_pos + outward_direction(track2, track1.ids.own, end_pos) * handle_length,
+                end_pos,
+            },
+        });
+        next_free_id += 1;
+    }
+    repairs
+}
+
+/// Attribute columns (type, max speed, electrification, isolation, name, ...) of a "Track" or "BTrack" row
+fn attribute_cells(row: &SceneryRow) -> Option<&[String]> {
+    if row.kind() != "Track" {
+        return None;
+    }
+    let start = if row.cells.get(2).is_some_and(|x| x == "BTrack") { 17 } else { 13 };
+    row.cells.get(start..)
+}
+
+/// Replaces the link from track `own` to `old` with `new`, in a "Track" or "TrackStructure" row.
+/// Returns false if the link was not found.
+fn replace_link(row: &mut SceneryRow, own: i32, old: i32, new: i32) -> bool {
+    let (old, new) = (old.to_string(), new.to_string());
+    if row.kind() == "TrackStructure" {
+        let Some(subtracks) = row.cells.get(9) else {
+            return false;
+        };
+        let mut found = false;
+        let replaced: Vec<String> = subtracks
+            .split(',')
+            .map(|part| {
+                let mut ids: Vec<&str> = part.split(':').collect();
+                if ids.len() == 3 && ids[0] == own.to_string() {
+                    for id in &mut ids[1..] {
+                        if *id == old {
+                            *id = &new;
+                            found = true;
+                        }
+                    }
+                }
+                ids.join(":")
+            })
+            .collect();
+        row.set_cell(9, replaced.join(","));
+        return found;
+    }
+
+    // Next and previous track IDs of "Track" and "BTrack" kinds
+    let link_cells = match row.cells.get(2).map(String::as_str) {
+        Some("BTrack") => [15, 16],
+        _ => [11, 12],
+    };
+    let mut found = false;
+    for index in link_cells {
+        if row.cells.get(index) == Some(&old) {
+            row.set_cell(index, new.clone());
+            found = true;
+        }
+    }
+    found
+}
+
+impl ParseResult {
+    /// Applies the repairs to the rows of the scenery, so that they can be saved with `write`.
+    /// The parsed tracks are not updated.
+    pub fn apply_repairs(&mut self, repairs: &[Repair]) -> anyhow::Result<()> {
+        for repair in repairs {
+            let row_index = self.rows.iter().position(|x| x.line == Some(repair.line))
+                .with_context(|| format!("No row at line {}", repair.line))?;
+            match &repair.action {
+                RepairAction::SetLength { length, .. } => {
+                    self.rows[row_index].set_cell(9, format_number(*length));
+                },
+                RepairAction::MoveEnd { track_id, at_end, pos } => {
+                    let shape = &self.tracks[self.track_indexes[track_id]].shape;
+                    let old_pos = if *at_end { shape.end().pos } else { shape.start().pos };
+                    let start = shape.start().pos + (*pos - old_pos);
+                    set_position(&mut self.rows[row_index], 3, start);
+                },
+                RepairAction::InsertConnector { track_id, prev_id, next_id, start_pos, control1, control2, end_pos } => {
+                    let prev_line = self.tracks[self.track_indexes[prev_id]].line;
+                    let next_line = self.tracks[self.track_indexes[next_id]].line;
+                    // The connector continues the first track, so it gets its attributes,
+                    // or the ones of the second track if the first one is part of a track structure
+                    let attributes: Vec<String> = [prev_line, next_line]
+                        .iter()
+                        .filter_map(|line| self.rows.iter().find(|x| x.line.is_some() && x.line == *line))
+                        .find_map(|row| attribute_cells(row).map(<[String]>::to_vec))
+                        .unwrap_or_default();
+                    // The link back is missing if it was one-way, so only the first track has to link to the second
+                    for (line, own, old, required) in [(prev_line, prev_id, next_id, true), (next_line, next_id, prev_id, false)] {
+                        let Some(row) = self.rows.iter_mut().find(|x| x.line.is_some() && x.line == line) else {
+                            bail!("No row for track {own}");
+                        };
+                        if !replace_link(row, *own, *old, *track_id) && required {
+                            bail!("Track {own} does not link to track {old}");
+                        }
+                    }
+
+                    let line_ending = self.rows
+                        .iter()
+                        .map(|x| x.line_ending)
+                        .find(|x| *x != LineEnding::None)
+                        .unwrap_or(LineEnding::CrLf);
+                    let cells = vec!["Track".to_string(), track_id.to_string(), "BTrack".to_string()];
+                    let mut row = SceneryRow::new(cells, line_ending);
+                    set_position(&mut row, 3, *start_pos);
+                    set_position(&mut row, 6, *control1 - *start_pos);
+                    set_position(&mut row, 9, *end_pos);
+                    set_position(&mut row, 12, *control2 - *end_pos);
+                    row.set_cell(15, next_id.to_string());
+                    row.set_cell(16, prev_id.to_string());
+                    row.set_cell(17, "");
+                    for (offset, value) in attributes.into_iter().enumerate() {
+                        row.set_cell(17 + offset, value);
+                    }
+
+                    // Keep the file ending the same way, if the connector is added after the last row
+                    let previous_row = &mut self.rows[row_index];
+                    if previous_row.line_ending == LineEnding::None {
+                        previous_row.line_ending = line_ending;
+                        row.line_ending = LineEnding::None;
+                    }
+                    self.rows.insert(row_index + 1, row);
+                },
+            }
+        }
+        Ok(())
+    }
+}
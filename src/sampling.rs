@@ -0,0 +1,182 @@
+use crate::math::{rotation_from_direction, GeoNdPoint, Vec3Ext};
+use crate::parse::{Checkpoint, Track, TrackShape};
+use bezier_nd::Bezier;
+use glam::{Mat3, Vec3};
+
+/// Number of equal parameter steps in the arc-length table of a bezier
+static BEZIER_TABLE_STEPS: usize = 32;
+/// Smallest chord error accepted by the tessellation, so that it always terminates
+static MIN_CHORD_ERROR: f32 = 0.001;
+/// Maximum number of times a bezier is split in half while tessellating
+static MAX_BEZIER_DEPTH: u32 = 16;
+
+pub(crate) type CubicBezier = Bezier<f32, GeoNdPoint, 3>;
+
+pub(crate) fn cubic_bezier(start_pos: Vec3, control1: Vec3, control2: Vec3, end_pos: Vec3) -> CubicBezier {
+    Bezier::cubic(
+        &start_pos.to_geo_nd(),
+        &control1.to_geo_nd(),
+        &control2.to_geo_nd(),
+        &end_pos.to_geo_nd(),
+    )
+}
+
+fn to_vec3(point: GeoNdPoint) -> Vec3 {
+    Vec3::new(point[0], point[1], point[2])
+}
+
+/// Cumulative lengths of a bezier at evenly spaced values of its parameter
+#[derive(Debug, Clone)]
+pub(crate) struct ArcLengthTable {
+    /// `lengths[i]` is the length of the curve between t = 0 and t = i / BEZIER_TABLE_STEPS
+    lengths: Vec<f32>,
+}
+
+impl ArcLengthTable {
+    pub(crate) fn new(bezier: &CubicBezier, straightness: f32) -> Self {
+        let mut lengths = Vec::with_capacity(BEZIER_TABLE_STEPS + 1);
+        let mut total = 0.0;
+        lengths.push(total);
+        for i in 0..BEZIER_TABLE_STEPS {
+            let t0 = i as f32 / BEZIER_TABLE_STEPS as f32;
+            let t1 = (i + 1) as f32 / BEZIER_TABLE_STEPS as f32;
+            total += bezier.bezier_between(t0, t1).length(straightness);
+            lengths.push(total);
+        }
+        ArcLengthTable { lengths }
+    }
+
+    pub(crate) fn total_length(&self) -> f32 {
+        *self.lengths.last().unwrap()
+    }
+
+    /// Parameter of the point at `distance` from the start, interpolated linearly between the table entries
+    pub(crate) fn t_at(&self, distance: f32) -> f32 {
+        let distance = distance.clamp(0.0, self.total_length());
+        let i = self.lengths.partition_point(|x| *x < distance).clamp(1, BEZIER_TABLE_STEPS);
+        let (before, after) = (self.lengths[i - 1], self.lengths[i]);
+        let fraction = if after > before { (distance - before) / (after - before) } else { 0.0 };
+        (i as f32 - 1.0 + fraction) / BEZIER_TABLE_STEPS as f32
+    }
+}
+
+/// Position and heading at some distance along a track
+#[derive(Debug, Copy, Clone)]
+pub struct TrackPoint {
+    pub pos: Vec3,
+    /// Rotation turning `Vec3::Z` into the direction of travel
+    pub rotation: Mat3,
+}
+
+impl TrackPoint {
+    /// Unit vector pointing in the direction of travel
+    pub fn direction(&self) -> Vec3 {
+        self.rotation * Vec3::Z
+    }
+}
+
+impl TrackShape {
+    pub(crate) fn length(&self) -> f32 {
+        match self {
+            TrackShape::Straight { length, .. } => *length,
+            TrackShape::Arc { length, .. } => *length,
+            TrackShape::Bezier { arc_lengths, .. } => arc_lengths.total_length(),
+            TrackShape::Point(_) => 0.0,
+        }
+    }
+
+    /// Returns the checkpoint at `distance` from the start, clamped to the length of the shape
+    pub(crate) fn sample(&self, distance: f32) -> Checkpoint {
+        let distance = distance.clamp(0.0, self.length());
+        match self {
+            TrackShape::Straight { start, .. } => Checkpoint {
+                pos: start.pos + start.rotation * distance * Vec3::Z,
+                rotation: start.rotation,
+            },
+            TrackShape::Arc { start_pos, rotated_circle, .. } => {
+                rotated_circle.move_by_angle(*start_pos, distance / rotated_circle.original_radius().abs())
+            },
+            TrackShape::Bezier { start_pos, control1, control2, end_pos, arc_lengths } => {
+                let bezier = cubic_bezier(*start_pos, *control1, *control2, *end_pos);
+                let t = arc_lengths.t_at(distance);
+                let rotation = rotation_from_direction(to_vec3(bezier.tangent_at(t)))
+                    .unwrap_or_else(|| if t < 0.5 { self.start().rotation } else { self.end().rotation });
+                Checkpoint { pos: to_vec3(bezier.point_at(t)), rotation }
+            },
+            TrackShape::Point(point) => *point,
+        }
+    }
+
+    /// Approximates the shape with a polyline whose segments are at most `max_error` away from the curve
+    pub(crate) fn tessellate(&self, max_error: f32) -> Vec<Vec3> {
+        let max_error = max_error.max(MIN_CHORD_ERROR);
+        match self {
+            TrackShape::Straight { start, end_pos, .. } => vec![start.pos, *end_pos],
+            TrackShape::Arc { start_pos, angle, rotated_circle, .. } => {
+                // The sagitta of a chord spanning `step` is radius * (1 - cos(step / 2))
+                let radius = rotated_circle.original_radius().abs();
+                let max_step = 2.0 * (1.0 - (max_error / radius).min(1.0)).acos();
+                let segments = (angle / max_step).ceil().max(1.0) as usize;
+                (0..=segments)
+                    .map(|i| rotated_circle.move_by_angle(*start_pos, angle * i as f32 / segments as f32).pos)
+                    .collect()
+            },
+            TrackShape::Bezier { start_pos, control1, control2, end_pos, .. } => {
+                let mut polyline = vec![*start_pos];
+                tessellate_bezier([*start_pos, *control1, *control2, *end_pos], max_error, 0, &mut polyline);
+                polyline
+            },
+            TrackShape::Point(point) => vec![point.pos],
+        }
+    }
+}
+
+impl Track {
+    /// Length of the track measured along its centre line
+    pub fn length(&self) -> f32 {
+        self.shape.length()
+    }
+
+    /// Returns the position and heading at `distance` from the start of the track.
+    /// The distance is clamped to the length of the track.
+    pub fn sample(&self, distance: f32) -> TrackPoint {
+        let checkpoint = self.shape.sample(distance);
+        TrackPoint { pos: checkpoint.pos, rotation: checkpoint.rotation }
+    }
+
+    /// Approximates the track with a 3D polyline, deviating from the real geometry by at most `max_error`
+    pub fn polyline(&self, max_error: f32) -> Vec<Vec3> {
+        self.shape.tessellate(max_error)
+    }
+}
+
+fn distance_to_segment(point: Vec3, start: Vec3, end: Vec3) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    let t = if length_squared > 0.0 {
+        ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (start + segment * t - point).length()
+}
+
+/// Appends the points of a cubic bezier after its start, splitting it in half until the control points
+/// (and thus the whole curve) are within `max_error` of the chord.
+fn tessellate_bezier(points: [Vec3; 4], max_error: f32, depth: u32, polyline: &mut Vec<Vec3>) {
+    let [p0, c1, c2, p3] = points;
+    let flat = distance_to_segment(c1, p0, p3) <= max_error && distance_to_segment(c2, p0, p3) <= max_error;
+    if flat || depth >= MAX_BEZIER_DEPTH {
+        polyline.push(p3);
+        return;
+    }
+    // De Casteljau split at t = 0.5
+    let p01 = (p0 + c1) / 2.0;
+    let p12 = (c1 + c2) / 2.0;
+    let p23 = (c2 + p3) / 2.0;
+    let p012 = (p01 + p12) / 2.0;
+    let p123 = (p12 + p23) / 2.0;
+    let middle = (p012 + p123) / 2.0;
+    tessellate_bezier([p0, p01, p012, middle], max_error, depth + 1, polyline);
+    tessellate_bezier([middle, p123, p23, p3], max_error, depth + 1, polyline);
+}
@@ -0,0 +1,110 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Last line of a file without a trailing newline
+    None,
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::None => "",
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// A row of a scenery file, with all cells kept verbatim
+#[derive(Debug, Clone)]
+pub struct SceneryRow {
+    /// 1-based line number in the original file, `None` for rows added after parsing
+    pub line: Option<usize>,
+    pub cells: Vec<String>,
+    pub line_ending: LineEnding,
+    /// Bytes of the line as read, without the line ending. Written instead of the cells while they are unchanged.
+    raw: Option<Vec<u8>>,
+}
+
+impl SceneryRow {
+    pub fn new(cells: Vec<String>, line_ending: LineEnding) -> Self {
+        SceneryRow { line: None, cells, line_ending, raw: None }
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.cells[0]
+    }
+
+    /// If not, the cells were decoded with replacement characters
+    pub fn is_valid_utf8(&self) -> bool {
+        self.raw.as_ref().is_none_or(|x| std::str::from_utf8(x).is_ok())
+    }
+
+    /// The bytes read from the file, if the cells weren't changed since
+    fn unmodified_raw(&self) -> Option<&[u8]> {
+        let raw = self.raw.as_ref()?;
+        let raw_cells = String::from_utf8_lossy(raw);
+        raw_cells.split(';').eq(self.cells.iter().map(String::as_str)).then_some(raw.as_slice())
+    }
+
+    /// Sets the cell, adding empty cells if the row is too short
+    pub fn set_cell(&mut self, index: usize, value: impl Into<String>) {
+        if self.cells.len() <= index {
+            self.cells.resize(index + 1, String::new());
+        }
+        self.cells[index] = value.into();
+    }
+}
+
+/// Formats a number the way it should be written to a scenery file
+pub fn format_number(value: f32) -> String {
+    if value == 0.0 {
+        // Avoid writing "-0"
+        return "0".to_string();
+    }
+    value.to_string()
+}
+
+pub(crate) fn read_rows<R: Read>(input: R) -> anyhow::Result<Vec<SceneryRow>> {
+    let mut reader = BufReader::new(input);
+    let mut rows: Vec<SceneryRow> = vec![];
+    let mut buffer: Vec<u8> = vec![];
+    loop {
+        buffer.clear();
+        if reader.read_until(b'\n', &mut buffer)? == 0 {
+            break;
+        }
+        let (content, line_ending) = if let Some(content) = buffer.strip_suffix(b"\r\n") {
+            (content, LineEnding::CrLf)
+        } else if let Some(content) = buffer.strip_suffix(b"\n") {
+            (content, LineEnding::Lf)
+        } else {
+            (buffer.as_slice(), LineEnding::None)
+        };
+        rows.push(SceneryRow {
+            line: Some(rows.len() + 1),
+            cells: String::from_utf8_lossy(content).split(';').map(|x| x.to_string()).collect(),
+            line_ending,
+            raw: Some(content.to_vec()),
+        });
+    }
+    Ok(rows)
+}
+
+/// Writes the rows back in the semicolon-separated scenery format.
+/// Unmodified rows are written exactly as they were read.
+pub fn write_rows<W: Write>(rows: &[SceneryRow], writer: W) -> anyhow::Result<()> {
+    let mut writer = std::io::BufWriter::new(writer);
+    for row in rows {
+        match row.unmodified_raw() {
+            Some(raw) => writer.write_all(raw)?,
+            None => writer.write_all(row.cells.join(";").as_bytes())?,
+        }
+        writer.write_all(row.line_ending.as_str().as_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
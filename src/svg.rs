@@ -1,10 +1,16 @@
 use crate::math::{project_circle, project_pos};
-use crate::parse::{ParseResult, Track, TrackShape};
+use crate::misc::MiscTree;
+use crate::parse::{JointMismatch, ParseResult, Track, TrackShape};
+use crate::terrain::Terrain;
+use crate::track_objects::{TrackObject, TrackObjectKind};
+use crate::track_path::TrackPath;
+use glam::{Vec2, Vec3};
+use lazy_regex::Regex;
 use std::fs;
 use std::path::Path;
 use svg::node::element;
 use svg::node::element::path::Data;
-use svg::node::element::Rectangle;
+use svg::node::element::{Circle, Group, Polygon, Rectangle, Text};
 use svg::{Document, Node};
 
 fn path_data(track_shape: &TrackShape) -> Data {
@@ -17,10 +23,9 @@ fn path_data(track_shape: &TrackShape) -> Data {
             let projected_start = project_pos(&start.pos);
             let projected_end = project_pos(end);
 
-            let data = Data::new()
+            Data::new()
                 .move_to((projected_start.x, projected_start.y))
-                .line_to((projected_end.x, projected_end.y));
-            data
+                .line_to((projected_end.x, projected_end.y))
         }
         TrackShape::Arc {
             start_pos,
@@ -30,9 +35,9 @@ fn path_data(track_shape: &TrackShape) -> Data {
         } => {
             let projected_start = project_pos(start_pos);
             let projected_end = project_pos(&end.pos);
-            let projected_circle = project_circle(&rotated_circle);
+            let projected_circle = project_circle(rotated_circle);
 
-            let data = Data::new()
+            Data::new()
                 .move_to((projected_start.x, projected_start.y))
                 .elliptical_arc_to((
                     projected_circle.major_axis.length(),
@@ -46,8 +51,7 @@ fn path_data(track_shape: &TrackShape) -> Data {
                     },
                     projected_end.x,
                     projected_end.y,
-                ));
-            data
+                ))
         }
         TrackShape::Bezier {
             start_pos: start,
@@ -61,7 +65,7 @@ fn path_data(track_shape: &TrackShape) -> Data {
             let projected_control2 = project_pos(control2);
             let projected_end = project_pos(end);
 
-            let data = Data::new()
+            Data::new()
                 .move_to((projected_start.x, projected_start.y))
                 .cubic_curve_to((
                     projected_control1.x,
@@ -70,8 +74,7 @@ fn path_data(track_shape: &TrackShape) -> Data {
                     projected_control2.y,
                     projected_end.x,
                     projected_end.y,
-                ));
-            data
+                ))
         }
         TrackShape::Point(point) => {
             let projected_point = project_pos(&point.pos);
@@ -82,14 +85,196 @@ fn path_data(track_shape: &TrackShape) -> Data {
     }
 }
 
+/// Draws every sample of the terrain height grid as a square,
+/// lightened or darkened depending on how much it faces the light.
+fn hillshade(terrain: &Terrain) -> Option<Group> {
+    let grid = terrain.grid()?;
+    let light = Vec3::new(-1.0, 1.0, -1.0).normalize();
+    let flat_shade = Vec3::Y.dot(light);
+
+    let mut group = Group::new().set("id", "hillshade");
+    for j in 0..grid.size_z {
+        for i in 0..grid.size_x {
+            let Some(normal) = grid.normal(i, j) else {
+                continue;
+            };
+            let shade = normal.dot(light).max(0.0) - flat_shade;
+            if shade.abs() < 0.01 {
+                continue;
+            }
+            let corner = grid.sample_pos(i, j) + Vec2::splat(grid.cell_size / 2.0);
+            let projected_corner = project_pos(&Vec3::new(corner.x, 0.0, corner.y));
+            group = group.add(
+                Rectangle::new()
+                    .set("x", projected_corner.x)
+                    .set("y", projected_corner.y)
+                    .set("width", grid.cell_size)
+                    .set("height", grid.cell_size)
+                    .set("fill", if shade > 0.0 { "#fff" } else { "#000" })
+                    .set("fill-opacity", (shade.abs() * 1.5).min(0.6)),
+            );
+        }
+    }
+    Some(group)
+}
+
+/// Draws signals with their names and a tick in the direction they face,
+/// along with speed signs, kilometre posts and stop markers.
+fn signals_layer(track_objects: &[TrackObject]) -> Group {
+    let mut group = Group::new().set("id", "signals");
+    for object in track_objects {
+        let (color, text) = match &object.kind {
+            TrackObjectKind::Signal { name } => ("#f44", name.clone()),
+            TrackObjectKind::SpeedSign { speed } => ("#fc3", speed.map(|x| x.to_string()).unwrap_or_default()),
+            TrackObjectKind::KilometrePost { kilometre } => ("#8cf", kilometre.map(|x| format!("{x:.3}")).unwrap_or_default()),
+            TrackObjectKind::StopMarker => ("#6d6", String::new()),
+            TrackObjectKind::Other => continue,
+        };
+        let pos = project_pos(&object.pos);
+        let facing = project_pos(&(object.pos + object.rotation * Vec3::Z * 3.0));
+
+        let mut object_group = Group::new()
+            .set("id", format!("track_object_{}", object.id))
+            .set(
+                "inkscape:label",
+                format!("{} {} on track {:?}", object.prefab, text, object.track_id),
+            )
+            .add(
+                element::Path::new()
+                    .set("d", Data::new().move_to((pos.x, pos.y)).line_to((facing.x, facing.y)))
+                    .set("stroke", color)
+                    .set("stroke-width", 0.4),
+            )
+            .add(
+                Circle::new()
+                    .set("cx", pos.x)
+                    .set("cy", pos.y)
+                    .set("r", 1.0)
+                    .set("fill", color),
+            );
+        if !text.is_empty() {
+            object_group = object_group.add(
+                Text::new(text)
+                    .set("x", pos.x + 1.5)
+                    .set("y", pos.y - 1.5)
+                    .set("font-size", 3.0)
+                    .set("font-family", "sans-serif")
+                    .set("fill", color),
+            );
+        }
+        group = group.add(object_group);
+    }
+    group
+}
+
+/// Draws misc objects as square icons rotated like the objects.
+/// If `filter` is not empty, only objects with a prefab name matching one of the patterns are drawn.
+fn misc_layer(misc: &MiscTree, filter: &[Regex]) -> Group {
+    static MISC_COLOR: &str = "#4a5a6a";
+    static ICON_HALF_SIZE: f32 = 2.0;
+
+    let mut group = Group::new().set("id", "misc");
+    for object in misc.objects() {
+        if !filter.is_empty() && !filter.iter().any(|x| x.is_match(&object.prefab)) {
+            continue;
+        }
+        let points: Vec<String> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .into_iter()
+            .map(|(x, z)| {
+                let corner = object.world.apply_to_pos(Vec3::new(x, 0.0, z) * ICON_HALF_SIZE);
+                let projected = project_pos(&corner);
+                format!("{},{}", projected.x, projected.y)
+            })
+            .collect();
+        group = group.add(
+            Polygon::new()
+                .set("id", format!("misc_{}", object.id))
+                .set("inkscape:label", object.prefab.clone())
+                .set("points", points.join(" "))
+                .set("fill", MISC_COLOR),
+        );
+    }
+    group
+}
+
+/// Builds a `<view>` element, which can be opened by linking to the SVG with `#id`
+fn named_view(id: &str, view_box: (i64, i64, i64, i64)) -> element::Element {
+    let mut view = element::Element::new("view");
+    view.assign("id", id);
+    view.assign("viewBox", view_box);
+    view
+}
+
 struct MapElement {
     y: f32,
     node: Box<dyn Node>,
 }
 
+/// View of the map shown when the SVG is opened.
+/// Both are also available as named views: `#extent` and `#home`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum InitialView {
+    /// All tracks with a margin
+    #[default]
+    FullExtent,
+    /// Centred on the home camera position of the scenery, falling back to the full extent
+    Home,
+}
+
+/// Options for `create_svg_with_options`
+#[derive(Debug, Clone, Default)]
+pub struct SvgOptions {
+    /// Draw a hillshade of the terrain under the tracks
+    pub hillshade: bool,
+    /// Draw signals and other track objects above the tracks
+    pub signals: bool,
+    /// Draw buildings, platforms and other misc objects under the tracks
+    pub misc_objects: bool,
+    /// Prefab name patterns of the misc objects to draw. All are drawn if empty.
+    pub misc_prefab_filter: Vec<Regex>,
+    /// Draw tracks without overhead wires in a different colour
+    pub electrification: bool,
+    /// Draw in world coordinates instead of the local frame of the scenery
+    pub world_frame: bool,
+    pub initial_view: InitialView,
+    /// Path drawn in a highlight colour, e.g. from `ParseResult::find_path`
+    pub highlight: Option<TrackPath>,
+    /// Draw every connected component of the track network in a different colour
+    pub components: bool,
+}
+
 pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Result<()> {
+    create_svg_with_options(parse_result, output_path, &SvgOptions::default())
+}
+
+pub fn create_svg_with_options(
+    parse_result: &ParseResult,
+    output_path: &Path,
+    options: &SvgOptions,
+) -> anyhow::Result<()> {
+    let world_parse_result;
+    let parse_result = if options.world_frame {
+        world_parse_result = parse_result.to_world_frame();
+        &world_parse_result
+    } else {
+        parse_result
+    };
+
     static BG_COLOR: &str = "#11202D";
     static TRACK_COLOR: &str = "#eee";
+    static ROUTE_TRACK_COLOR: &str = "#9ab";
+    static UNELECTRIFIED_TRACK_COLOR: &str = "#c96";
+    static HIGHLIGHT_COLOR: &str = "#4cf";
+    static COMPONENT_COLORS: [&str; 8] = ["#eee", "#6cf", "#fc6", "#9e6", "#f9c", "#c9f", "#6ec", "#fa8"];
+
+    let mut component_colors: Vec<Option<&str>> = vec![None; parse_result.tracks.len()];
+    if options.components {
+        for (index, component) in parse_result.components().iter().enumerate() {
+            for track_index in &component.track_indexes {
+                component_colors[*track_index] = Some(COMPONENT_COLORS[index % COMPONENT_COLORS.len()]);
+            }
+        }
+    }
 
     let mut document = Document::new();
 
@@ -100,10 +285,23 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
     let mut min_z: f32 = f32::MAX;
     let mut max_z: f32 = f32::MIN;
 
+    // Without any catenary in the scenery every track would be labelled
+    let label_unelectrified = options.electrification && !parse_result.catenary.is_empty();
+
     let mut add_track = |track: &Track, highlight: Option<&str>| {
         let data = path_data(&track.shape);
 
-        let label = format!(
+        let route = track.route.map(|index| &parse_result.routes[index]);
+
+        let track_color = if options.electrification && !track.electrified {
+            UNELECTRIFIED_TRACK_COLOR
+        } else if route.is_some() {
+            ROUTE_TRACK_COLOR
+        } else {
+            TRACK_COLOR
+        };
+
+        let mut label = format!(
             "Track {}, prev: {}, next: {:?}.\nShape: {:?}",
             track.ids.own,
             track
@@ -116,6 +314,12 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
                 .next,
             track.shape,
         );
+        if let Some(route) = route {
+            label += &format!("\nRoute: {}", route.name);
+        }
+        if label_unelectrified && !track.electrified {
+            label += "\nNot electrified";
+        }
 
         let background_path = element::Path::new()
             .set("d", data.clone())
@@ -125,12 +329,22 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
             .set("stroke-width", 8.0)
             .set("stroke-linecap", "round");
 
+        let attributes = &track.attributes;
         let track_path = element::Path::new()
             .set("id", format!("track_{}", track.ids.own))
             .set("inkscape:label", label.clone())
+            .set("data-track-type", attributes.track_type.clone())
+            .set("data-max-speed", attributes.max_speed.map(|x| x.to_string()).unwrap_or_default())
+            .set(
+                "data-electrification",
+                attributes.electrification.map(|x| x.to_string()).unwrap_or_default(),
+            )
+            .set("data-isolation", attributes.isolation.clone())
+            .set("data-name", attributes.name.clone())
+            .set("data-electrified", track.electrified.to_string())
             .set("d", data.clone())
             .set("fill", "none")
-            .set("stroke", highlight.unwrap_or(TRACK_COLOR))
+            .set("stroke", highlight.unwrap_or(track_color))
             .set("stroke-width", 1.2)
             .set("stroke-linecap", "round");
 
@@ -165,8 +379,13 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
         max_z = max_z.max(projected_start.y).max(projected_end.y);
     };
 
-    for track in &parse_result.tracks {
-        add_track(&track, None);
+    for (index, track) in parse_result.tracks.iter().enumerate() {
+        let highlight = options.highlight.as_ref().is_some_and(|x| x.contains(index));
+        add_track(track, highlight.then_some(HIGHLIGHT_COLOR).or(component_colors[index]));
+    }
+
+    if parse_result.tracks.is_empty() {
+        (min_x, max_x, min_z, max_z) = (0.0, 0.0, 0.0, 0.0);
     }
 
     let min_x = min_x as i64 - 100;
@@ -174,16 +393,48 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
     let min_z = min_z as i64 - 100;
     let max_z = max_z as i64 + 100;
 
+    let extent_view = (min_x, min_z, max_x - min_x, max_z - min_z);
+    let home_view = parse_result.home_viewport().map(|viewport| {
+        let center = project_pos(&viewport.center);
+        let size = viewport.width as i64;
+        (center.x as i64 - size / 2, center.y as i64 - size / 2, size, size)
+    });
+
+    // The background has to cover the home view too, in case it reaches outside the extent
+    let (bg_min_x, bg_min_z, bg_max_x, bg_max_z) = match home_view {
+        Some((x, z, width, height)) => (min_x.min(x), min_z.min(z), max_x.max(x + width), max_z.max(z + height)),
+        None => (min_x, min_z, max_x, max_z),
+    };
+
+    let initial_view = match options.initial_view {
+        InitialView::Home => home_view.unwrap_or(extent_view),
+        InitialView::FullExtent => extent_view,
+    };
+
     document = document
-        .set("viewBox", (min_x, min_z, max_x - min_x, max_z - min_z))
+        .set("viewBox", initial_view)
+        .add(named_view("extent", extent_view))
         .add(
             Rectangle::new()
-                .set("x", min_x)
-                .set("y", min_z)
-                .set("width", max_x - min_x)
-                .set("height", max_z - min_z)
+                .set("x", bg_min_x)
+                .set("y", bg_min_z)
+                .set("width", bg_max_x - bg_min_x)
+                .set("height", bg_max_z - bg_min_z)
                 .set("fill", BG_COLOR),
         );
+    if let Some(home_view) = home_view {
+        document = document.add(named_view("home", home_view));
+    }
+
+    if options.hillshade {
+        if let Some(hillshade) = hillshade(&parse_result.terrain) {
+            document = document.add(hillshade);
+        }
+    }
+
+    if options.misc_objects {
+        document = document.add(misc_layer(&parse_result.misc, &options.misc_prefab_filter));
+    }
 
     map_elements.sort_by_key(|x| x.y as i64);
 
@@ -191,6 +442,10 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
         document = document.add(element.node)
     }
 
+    if options.signals {
+        document = document.add(signals_layer(&parse_result.track_objects));
+    }
+
     for failed_connection in &parse_result.failed_connections {
         let start = project_pos(&failed_connection.pos1);
         let end = project_pos(&failed_connection.pos2);
@@ -198,22 +453,73 @@ pub fn create_svg(parse_result: &ParseResult, output_path: &Path) -> anyhow::Res
             .move_to((start.x, start.y))
             .line_to((end.x, end.y));
 
-        let failed_path = element::Path::new()
+        let color = match failed_connection.mismatch {
+            JointMismatch::Gap => "#f00",
+            JointMismatch::HeightStep => "#f80",
+            JointMismatch::Kink => "#f48",
+        };
+        // Height steps and kinks have (almost) coinciding ends, so they are marked with a circle instead
+        let marker: Box<dyn Node> = if failed_connection.mismatch == JointMismatch::Gap {
+            Box::new(element::Path::new().set("d", data))
+        } else {
+            Box::new(Circle::new().set("cx", start.x).set("cy", start.y).set("r", 2.0))
+        };
+
+        let failed_path = Group::new()
             .set(
                 "inkscape:label",
                 format!(
-                    "Failed connection from {} to {}",
-                    failed_connection.track1.ids.own, failed_connection.track2.ids.own
+                    "Failed connection ({:?}) from {} to {}",
+                    failed_connection.mismatch, failed_connection.track1.ids.own, failed_connection.track2.ids.own
                 ),
             )
-            .set("d", data)
+            .add(marker)
             .set("fill", "none")
-            .set("stroke", "#f00")
+            .set("stroke", color)
             .set("stroke-width", 1.0);
 
         document = document.add(failed_path);
     }
 
+    for link_problem in &parse_result.link_problems {
+        let pos = project_pos(&link_problem.pos);
+        let size = 2.5;
+        let data = Data::new()
+            .move_to((pos.x - size, pos.y - size))
+            .line_to((pos.x + size, pos.y + size))
+            .move_to((pos.x - size, pos.y + size))
+            .line_to((pos.x + size, pos.y - size));
+        let marker = element::Path::new()
+            .set("inkscape:label", link_problem.description())
+            .set("d", data)
+            .set("fill", "none")
+            .set("stroke", "#f0f")
+            .set("stroke-width", 1.0);
+
+        document = document.add(marker);
+    }
+
+    for track in parse_result.tracks.iter().filter(|x| x.is_placeholder()) {
+        let pos = project_pos(&track.shape.start().pos);
+        let marker = Circle::new()
+            .set(
+                "inkscape:label",
+                format!(
+                    "Placeholder for track {} of unknown kind {}",
+                    track.ids.own,
+                    track.placeholder_for.as_deref().unwrap_or_default(),
+                ),
+            )
+            .set("cx", pos.x)
+            .set("cy", pos.y)
+            .set("r", 3.0)
+            .set("fill", "none")
+            .set("stroke", "#fa0")
+            .set("stroke-width", 1.0);
+
+        document = document.add(marker);
+    }
+
     if let Some(dir) = output_path.parent() {
         fs::create_dir_all(dir)?;
     }
@@ -0,0 +1,241 @@
+use crate::transform::Transform;
+use anyhow::ensure;
+use glam::{Vec2, Vec3};
+use std::sync::OnceLock;
+
+/// Default distance between neighbouring samples of the height grid
+static DEFAULT_CELL_SIZE: f32 = 10.0;
+/// How many grid cells away from a sample the search keeps widening to find `MIN_NEIGHBOURS` points.
+/// Further away, the closest points found are used.
+static SEARCH_RADIUS_CELLS: i64 = 4;
+/// Samples with no terrain point within this many grid cells are left empty
+static MAX_SEARCH_RADIUS_CELLS: i64 = 16;
+/// Minimum number of terrain points used to interpolate a single sample
+static MIN_NEIGHBOURS: usize = 4;
+/// Largest number of samples in the height grid. The cell size is doubled until the grid fits.
+static MAX_SAMPLES: f64 = 1_000_000.0;
+
+/// Regular grid of ground heights covering the bounding box of the terrain points
+#[derive(Debug, Clone)]
+pub struct HeightGrid {
+    /// Position of the sample with indexes (0, 0), in the XZ plane
+    pub origin: Vec2,
+    pub cell_size: f32,
+    /// Number of samples along the X-axis
+    pub size_x: usize,
+    /// Number of samples along the Z-axis
+    pub size_z: usize,
+    /// Heights in row-major order, rows along the Z-axis. `None` for samples far from all terrain points.
+    heights: Vec<Option<f32>>,
+}
+
+impl HeightGrid {
+    fn build(points: &[Vec3], mut cell_size: f32) -> Option<Self> {
+        // A single point at infinity would stretch the grid over everything
+        let points: Vec<Vec3> = points.iter().copied().filter(|p| p.is_finite()).collect();
+        if points.len() < 3 || cell_size.is_nan() || cell_size <= 0.0 {
+            return None;
+        }
+        let min = points.iter().fold(Vec2::MAX, |acc, p| acc.min(Vec2::new(p.x, p.z)));
+        let max = points.iter().fold(Vec2::MIN, |acc, p| acc.max(Vec2::new(p.x, p.z)));
+        // Computed in f64, as the distance between far apart points can overflow f32
+        let cells_between = |from: f32, to: f32, cell_size: f32| ((to as f64 - from as f64) / cell_size as f64).round();
+        let samples_along = |from: f32, to: f32, cell_size: f32| ((to as f64 - from as f64) / cell_size as f64).ceil() + 1.0;
+        while samples_along(min.x, max.x, cell_size) * samples_along(min.y, max.y, cell_size) > MAX_SAMPLES {
+            cell_size *= 2.0;
+        }
+        let size_x = samples_along(min.x, max.x, cell_size) as usize;
+        let size_z = samples_along(min.y, max.y, cell_size) as usize;
+
+        // Bucket the points by grid cell, so that the interpolation only looks at nearby points
+        let mut buckets: Vec<Vec<Vec3>> = vec![vec![]; size_x * size_z];
+        for point in &points {
+            let i = cells_between(min.x, point.x, cell_size) as usize;
+            let j = cells_between(min.y, point.z, cell_size) as usize;
+            buckets[j * size_x + i].push(*point);
+        }
+
+        // Number of points in the buckets before (i, j) along both axes,
+        // to skip the search around the samples far from all points
+        let mut counts = vec![0; (size_x + 1) * (size_z + 1)];
+        for j in 0..size_z {
+            for i in 0..size_x {
+                counts[(j + 1) * (size_x + 1) + i + 1] = buckets[j * size_x + i].len()
+                    + counts[j * (size_x + 1) + i + 1]
+                    + counts[(j + 1) * (size_x + 1) + i]
+                    - counts[j * (size_x + 1) + i];
+            }
+        }
+        let radius = MAX_SEARCH_RADIUS_CELLS as usize;
+        let has_points_near = |i: usize, j: usize| {
+            let (i0, i1) = (i.saturating_sub(radius), (i + radius + 1).min(size_x));
+            let (j0, j1) = (j.saturating_sub(radius), (j + radius + 1).min(size_z));
+            counts[j1 * (size_x + 1) + i1] + counts[j0 * (size_x + 1) + i0]
+                > counts[j0 * (size_x + 1) + i1] + counts[j1 * (size_x + 1) + i0]
+        };
+
+        let mut heights = Vec::with_capacity(size_x * size_z);
+        for j in 0..size_z {
+            for i in 0..size_x {
+                if !has_points_near(i, j) {
+                    heights.push(None);
+                    continue;
+                }
+                let sample = min + Vec2::new(i as f32, j as f32) * cell_size;
+                heights.push(interpolate(&buckets, size_x, size_z, i, j, sample));
+            }
+        }
+
+        Some(HeightGrid { origin: min, cell_size, size_x, size_z, heights })
+    }
+
+    pub fn height(&self, i: usize, j: usize) -> Option<f32> {
+        self.heights[j * self.size_x + i]
+    }
+
+    /// Position of the sample in the XZ plane
+    pub fn sample_pos(&self, i: usize, j: usize) -> Vec2 {
+        self.origin + Vec2::new(i as f32, j as f32) * self.cell_size
+    }
+
+    /// Bilinear interpolation between the samples surrounding (x, z).
+    /// Returns `None` outside the grid or next to an empty sample.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let fx = (x - self.origin.x) / self.cell_size;
+        let fz = (z - self.origin.y) / self.cell_size;
+        if fx < 0.0 || fz < 0.0 || fx > (self.size_x - 1) as f32 || fz > (self.size_z - 1) as f32 {
+            return None;
+        }
+        let i = (fx.floor() as usize).min(self.size_x.saturating_sub(2));
+        let j = (fz.floor() as usize).min(self.size_z.saturating_sub(2));
+        let tx = (fx - i as f32).clamp(0.0, 1.0);
+        let tz = (fz - j as f32).clamp(0.0, 1.0);
+        let i1 = (i + 1).min(self.size_x - 1);
+        let j1 = (j + 1).min(self.size_z - 1);
+
+        let near = self.height(i, j)? * (1.0 - tx) + self.height(i1, j)? * tx;
+        let far = self.height(i, j1)? * (1.0 - tx) + self.height(i1, j1)? * tx;
+        Some(near * (1.0 - tz) + far * tz)
+    }
+
+    /// Surface normal at the sample, estimated from central differences.
+    /// Empty neighbouring samples are replaced by this one, `None` if it is empty itself.
+    pub fn normal(&self, i: usize, j: usize) -> Option<Vec3> {
+        let center = self.height(i, j)?;
+        let left = self.height(i.saturating_sub(1), j).unwrap_or(center);
+        let right = self.height((i + 1).min(self.size_x - 1), j).unwrap_or(center);
+        let near = self.height(i, j.saturating_sub(1)).unwrap_or(center);
+        let far = self.height(i, (j + 1).min(self.size_z - 1)).unwrap_or(center);
+        let dx = (right - left) / (2.0 * self.cell_size);
+        let dz = (far - near) / (2.0 * self.cell_size);
+        Some(Vec3::new(-dx, 1.0, -dz).normalize())
+    }
+}
+
+/// Inverse distance weighting of the points closest to the sample,
+/// found by searching rings of grid cells of growing radius around it
+fn interpolate(buckets: &[Vec<Vec3>], size_x: usize, size_z: usize, i: usize, j: usize, sample: Vec2) -> Option<f32> {
+    let mut neighbours: Vec<Vec3> = vec![];
+    for radius in 0..=MAX_SEARCH_RADIUS_CELLS {
+        for dj in -radius..=radius {
+            // Inside the ring only its left and right cells are new
+            let step = if dj.abs() == radius { 1 } else { 2 * radius as usize };
+            for di in (-radius..=radius).step_by(step) {
+                let (ni, nj) = (i as i64 + di, j as i64 + dj);
+                if ni < 0 || nj < 0 || ni >= size_x as i64 || nj >= size_z as i64 {
+                    continue;
+                }
+                neighbours.extend(&buckets[nj as usize * size_x + ni as usize]);
+            }
+        }
+        if neighbours.len() >= MIN_NEIGHBOURS || (radius >= SEARCH_RADIUS_CELLS && !neighbours.is_empty()) {
+            break;
+        }
+    }
+    if neighbours.is_empty() {
+        return None;
+    }
+
+    let mut weight_sum = 0.0;
+    let mut height_sum = 0.0;
+    for point in &neighbours {
+        let dist_squared = (Vec2::new(point.x, point.z) - sample).length_squared();
+        if dist_squared < 1e-6 {
+            return Some(point.y);
+        }
+        let weight = 1.0 / dist_squared;
+        weight_sum += weight;
+        height_sum += weight * point.y;
+    }
+    // The weights of points too far away underflow to zero
+    (weight_sum > 0.0).then(|| height_sum / weight_sum)
+}
+
+/// A "TerrainGroup" block, only used to keep track of which points were defined together
+#[derive(Debug, Clone)]
+pub struct TerrainGroup {
+    /// Line of the "TerrainGroup" row
+    pub line: usize,
+    /// Indexes in `Terrain::points`
+    pub point_indexes: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Terrain {
+    pub points: Vec<Vec3>,
+    pub groups: Vec<TerrainGroup>,
+    /// Distance between the samples of the height grid
+    cell_size: f32,
+    /// Built from the points on first use, as it is only needed for heights and the hillshade
+    grid: OnceLock<Option<HeightGrid>>,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain {
+            points: vec![],
+            groups: vec![],
+            cell_size: DEFAULT_CELL_SIZE,
+            grid: OnceLock::new(),
+        }
+    }
+}
+
+impl Terrain {
+    /// Height grid sampled from the points, `None` if there are too few of them.
+    /// Built on the first call, so the points shouldn't be changed afterwards.
+    pub fn grid(&self) -> Option<&HeightGrid> {
+        self.grid.get_or_init(|| HeightGrid::build(&self.points, self.cell_size)).as_ref()
+    }
+
+    /// Interpolates the ground height at (x, z).
+    /// Returns `None` if there is no terrain data at that position.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.grid()?.height_at(x, z)
+    }
+
+    /// Samples the terrain points into a height grid with the given cell size,
+    /// or a larger one if the grid would be too big
+    pub fn build_grid(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.grid = OnceLock::new();
+        self.grid();
+    }
+
+    /// Moves all the points. The height grid is built again when it is next needed.
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        for point in &mut self.points {
+            *point = transform.apply_to_pos(*point);
+        }
+        self.grid = OnceLock::new();
+    }
+}
+
+pub(crate) fn parse_terrain_point(cells: &[&str]) -> anyhow::Result<Vec3> {
+    ensure!(cells.len() >= 4);
+    Ok(Vec3 {
+        x: cells[1].parse()?,
+        y: cells[2].parse()?,
+        z: cells[3].parse()?,
+    })
+}
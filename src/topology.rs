@@ -0,0 +1,145 @@
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
+use crate::parse::Track;
+use crate::track_graph::{Direction, Edge, TrackGraph};
+use crate::transform::Transform;
+use glam::Vec3;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkProblemKind {
+    /// The track links to another one, which doesn't link back
+    OneWayLink,
+    /// The track links to an ID which doesn't exist
+    DanglingLink,
+    /// The track links to itself
+    SelfLoop,
+    /// A branch of the switch is not connected to anything
+    MissingBranch,
+}
+
+impl LinkProblemKind {
+    fn diagnostic_kind(self) -> DiagnosticKind {
+        match self {
+            LinkProblemKind::OneWayLink => DiagnosticKind::OneWayLink,
+            LinkProblemKind::DanglingLink => DiagnosticKind::DanglingLink,
+            LinkProblemKind::SelfLoop => DiagnosticKind::SelfLoop,
+            LinkProblemKind::MissingBranch => DiagnosticKind::MissingBranch,
+        }
+    }
+}
+
+/// A problem with the links between tracks, independent of their geometry
+#[derive(Debug, Clone)]
+pub struct LinkProblem {
+    pub kind: LinkProblemKind,
+    pub track_id: i32,
+    /// ID the track links to, if the problem is about a single link,
+    /// or the last track of the branch for a missing branch
+    pub other_id: Option<i32>,
+    /// Position of the track end with the problem
+    pub pos: Vec3,
+    /// Line of the track (or its track structure)
+    pub line: Option<usize>,
+}
+
+impl LinkProblem {
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        self.pos = transform.apply_to_pos(self.pos);
+    }
+
+    pub fn description(&self) -> String {
+        let other_id = self.other_id.map(|x| x.to_string()).unwrap_or_default();
+        match self.kind {
+            LinkProblemKind::OneWayLink => format!("Track {} links to track {other_id}, which doesn't link back", self.track_id),
+            LinkProblemKind::DanglingLink => format!("Track {} links to track {other_id}, which doesn't exist", self.track_id),
+            LinkProblemKind::SelfLoop => format!("Track {} links to itself", self.track_id),
+            LinkProblemKind::MissingBranch => {
+                format!("The branch of the switch at track {} ending at track {other_id} is not connected", self.track_id)
+            },
+        }
+    }
+
+    pub(crate) fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = match self.kind {
+            LinkProblemKind::DanglingLink | LinkProblemKind::SelfLoop => Diagnostic::error(self.description()),
+            _ => Diagnostic::warning(self.description()),
+        };
+        diagnostic
+            .at_line(self.line)
+            .with_id(Some(self.track_id))
+            .with_kind(self.kind.diagnostic_kind())
+    }
+}
+
+fn links_to(track: &Track, id: i32) -> bool {
+    track.ids.prev == Some(id) || track.ids.next.to_vec().contains(&id)
+}
+
+/// Checks that every link points to another existing track which links back
+fn check_links(tracks: &[Track], track_indexes: &HashMap<i32, usize>) -> Vec<LinkProblem> {
+    let mut problems = vec![];
+    for track in tracks {
+        let linked_ids = track.ids.prev.map(|x| (x, track.shape.start().pos))
+            .into_iter()
+            .chain(track.ids.next.to_vec().into_iter().map(|x| (x, track.shape.end().pos)));
+        for (id, pos) in linked_ids {
+            let kind = if id == track.ids.own {
+                LinkProblemKind::SelfLoop
+            } else if let Some(other_index) = track_indexes.get(&id) {
+                let other = &tracks[*other_index];
+                // Links of placeholders are unknown
+                if other.is_placeholder() || links_to(other, track.ids.own) {
+                    continue;
+                }
+                LinkProblemKind::OneWayLink
+            } else {
+                LinkProblemKind::DanglingLink
+            };
+            problems.push(LinkProblem { kind, track_id: track.ids.own, other_id: Some(id), pos, line: track.line });
+        }
+    }
+    problems
+}
+
+/// Follows every branch of every fork through its track structure,
+/// looking for branches which end without leaving the structure.
+fn check_branches(tracks: &[Track], graph: &TrackGraph) -> Vec<LinkProblem> {
+    let mut problems = vec![];
+    for (track_index, track) in tracks.iter().enumerate() {
+        if track.ids.next.to_vec().len() < 2 {
+            continue;
+        }
+        let stem = Edge { track_index, direction: Direction::Forward };
+        for branch in graph.next_edges(stem) {
+            let mut edge = branch;
+            for _ in 0..tracks.len() {
+                let next: Vec<Edge> = graph.next_edges(edge).collect();
+                if next.is_empty() {
+                    problems.push(LinkProblem {
+                        kind: LinkProblemKind::MissingBranch,
+                        track_id: track.ids.own,
+                        other_id: Some(tracks[edge.track_index].ids.own),
+                        pos: edge.arrival().pos(tracks),
+                        line: track.line,
+                    });
+                    break;
+                }
+                if next.len() > 1 || tracks[next[0].track_index].line != track.line {
+                    break;
+                }
+                edge = next[0];
+            }
+        }
+    }
+    problems
+}
+
+pub(crate) fn validate_links(
+    tracks: &[Track],
+    track_indexes: &HashMap<i32, usize>,
+    graph: &TrackGraph,
+) -> Vec<LinkProblem> {
+    let mut problems = check_links(tracks, track_indexes);
+    problems.extend(check_branches(tracks, graph));
+    problems
+}
@@ -0,0 +1,250 @@
+use crate::parse::{ParseResult, Track};
+use glam::Vec3;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TrackEnd {
+    Start,
+    End,
+}
+
+impl TrackEnd {
+    fn index(self) -> usize {
+        match self {
+            TrackEnd::Start => 0,
+            TrackEnd::End => 1,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            TrackEnd::Start => TrackEnd::End,
+            TrackEnd::End => TrackEnd::Start,
+        }
+    }
+}
+
+/// One of the two ends of a track, with the index in `ParseResult::tracks`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    pub track_index: usize,
+    pub end: TrackEnd,
+}
+
+impl Endpoint {
+    pub fn pos(&self, tracks: &[Track]) -> Vec3 {
+        let shape = &tracks[self.track_index].shape;
+        match self.end {
+            TrackEnd::Start => shape.start().pos,
+            TrackEnd::End => shape.end().pos,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// From the start of the track to its end
+    Forward,
+    Backward,
+}
+
+/// A track travelled in one direction
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub track_index: usize,
+    pub direction: Direction,
+}
+
+impl Edge {
+    /// The edge leaving a node through the given endpoint
+    pub fn leaving(endpoint: Endpoint) -> Self {
+        let direction = match endpoint.end {
+            TrackEnd::Start => Direction::Forward,
+            TrackEnd::End => Direction::Backward,
+        };
+        Edge { track_index: endpoint.track_index, direction }
+    }
+
+    pub fn reversed(self) -> Self {
+        let direction = match self.direction {
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+        };
+        Edge { track_index: self.track_index, direction }
+    }
+
+    /// Endpoint the edge starts from
+    pub fn departure(self) -> Endpoint {
+        let end = match self.direction {
+            Direction::Forward => TrackEnd::Start,
+            Direction::Backward => TrackEnd::End,
+        };
+        Endpoint { track_index: self.track_index, end }
+    }
+
+    /// Endpoint the edge arrives at
+    pub fn arrival(self) -> Endpoint {
+        let departure = self.departure();
+        Endpoint { track_index: departure.track_index, end: departure.end.opposite() }
+    }
+}
+
+/// Side of a fork, either the single track before the split or one of the branches
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ForkSide {
+    /// Travelling from it the switch position decides the branch
+    Facing,
+    /// Travelling from it the train can only continue to the facing side
+    Trailing,
+}
+
+/// A place where track ends meet
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub pos: Vec3,
+    pub endpoints: Vec<Endpoint>,
+}
+
+impl Node {
+    pub fn is_dead_end(&self) -> bool {
+        self.endpoints.len() == 1
+    }
+
+    /// More than two track ends meet, e.g. at the split of a switch
+    pub fn is_junction(&self) -> bool {
+        self.endpoints.len() > 2
+    }
+}
+
+/// Connectivity of the tracks, with a node at every place where track ends meet
+/// and two edges (one per direction) for every track.
+#[derive(Debug, Clone)]
+pub struct TrackGraph {
+    nodes: Vec<Node>,
+    /// Node index of both ends of every track
+    endpoint_nodes: Vec<[usize; 2]>,
+    /// Endpoints reachable from both ends of every track, following the connected track IDs
+    links: Vec<[Vec<Endpoint>; 2]>,
+}
+
+fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+impl TrackGraph {
+    /// Builds the graph from the connected track IDs. Links to unknown IDs are ignored.
+    pub fn new(tracks: &[Track], track_indexes: &HashMap<i32, usize>) -> Self {
+        let mut links: Vec<[Vec<Endpoint>; 2]> = vec![[vec![], vec![]]; tracks.len()];
+        for (track_index, track) in tracks.iter().enumerate() {
+            let linked_ids = [track.ids.prev.into_iter().collect(), track.ids.next.to_vec()];
+            for end in [TrackEnd::Start, TrackEnd::End] {
+                let endpoint = Endpoint { track_index, end };
+                for id in &linked_ids[end.index()] {
+                    let Some(&other_index) = track_indexes.get(id) else {
+                        continue;
+                    };
+                    let other_at_end = tracks[other_index].is_linked_at_end(track.ids.own, endpoint.pos(tracks));
+                    let other_end = if other_at_end { TrackEnd::End } else { TrackEnd::Start };
+                    links[track_index][end.index()].push(Endpoint { track_index: other_index, end: other_end });
+                }
+            }
+        }
+
+        // Linked endpoints are merged into nodes
+        let endpoint_index = |endpoint: Endpoint| endpoint.track_index * 2 + endpoint.end.index();
+        let mut parents: Vec<usize> = (0..tracks.len() * 2).collect();
+        for (track_index, track_links) in links.iter().enumerate() {
+            for end in [TrackEnd::Start, TrackEnd::End] {
+                for other in &track_links[end.index()] {
+                    let root = find_root(&mut parents, endpoint_index(Endpoint { track_index, end }));
+                    let other_root = find_root(&mut parents, endpoint_index(*other));
+                    parents[other_root] = root;
+                }
+            }
+        }
+
+        let mut nodes: Vec<Node> = vec![];
+        let mut root_nodes: HashMap<usize, usize> = HashMap::new();
+        let mut endpoint_nodes = vec![[0; 2]; tracks.len()];
+        for (track_index, track_nodes) in endpoint_nodes.iter_mut().enumerate() {
+            for end in [TrackEnd::Start, TrackEnd::End] {
+                let endpoint = Endpoint { track_index, end };
+                let root = find_root(&mut parents, endpoint_index(endpoint));
+                let node_index = *root_nodes.entry(root).or_insert_with(|| {
+                    nodes.push(Node { pos: endpoint.pos(tracks), endpoints: vec![] });
+                    nodes.len() - 1
+                });
+                nodes[node_index].endpoints.push(endpoint);
+                track_nodes[end.index()] = node_index;
+            }
+        }
+
+        TrackGraph { nodes, endpoint_nodes, links }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node_of(&self, endpoint: Endpoint) -> usize {
+        self.endpoint_nodes[endpoint.track_index][endpoint.end.index()]
+    }
+
+    /// All edges, two for every track
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        (0..self.endpoint_nodes.len()).flat_map(|track_index| {
+            [Direction::Forward, Direction::Backward].map(|direction| Edge { track_index, direction })
+        })
+    }
+
+    pub fn from(&self, edge: Edge) -> usize {
+        self.node_of(edge.departure())
+    }
+
+    pub fn to(&self, edge: Edge) -> usize {
+        self.node_of(edge.arrival())
+    }
+
+    /// Edges leaving the node, regardless of where the train came from
+    pub fn edges_from(&self, node: usize) -> impl Iterator<Item = Edge> + '_ {
+        self.nodes[node].endpoints.iter().map(|x| Edge::leaving(*x))
+    }
+
+    /// Endpoints linked to the given one by its track's connected IDs
+    pub fn linked(&self, endpoint: Endpoint) -> &[Endpoint] {
+        &self.links[endpoint.track_index][endpoint.end.index()]
+    }
+
+    /// Edges a train can continue on after travelling along `edge`, without reversing.
+    /// After coming from a branch of a fork only the facing side is allowed.
+    pub fn next_edges(&self, edge: Edge) -> impl Iterator<Item = Edge> + '_ {
+        self.linked(edge.arrival()).iter().map(|x| Edge::leaving(*x))
+    }
+
+    /// Nodes connected to the node by a single edge
+    pub fn neighbours(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges_from(node).map(|edge| self.to(edge))
+    }
+
+    /// Tells whether the endpoint is the facing side or a branch of a fork at its node
+    pub fn fork_side(&self, endpoint: Endpoint) -> Option<ForkSide> {
+        if self.linked(endpoint).len() > 1 {
+            return Some(ForkSide::Facing);
+        }
+        let node = &self.nodes[self.node_of(endpoint)];
+        node.endpoints
+            .iter()
+            .any(|x| self.linked(*x).len() > 1 && self.linked(*x).contains(&endpoint))
+            .then_some(ForkSide::Trailing)
+    }
+}
+
+impl ParseResult {
+    pub fn track_graph(&self) -> TrackGraph {
+        TrackGraph::new(&self.tracks, &self.track_indexes)
+    }
+}
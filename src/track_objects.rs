@@ -0,0 +1,76 @@
+use crate::parse::{parse_position, parse_transform};
+use crate::transform::Transform;
+use anyhow::ensure;
+use glam::{Mat3, Vec3};
+use lazy_regex::regex_is_match;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackObjectKind {
+    Signal { name: String },
+    /// W8 speed indicator, with the speed in km/h
+    SpeedSign { speed: Option<u32> },
+    KilometrePost { kilometre: Option<f32> },
+    /// W4 stop marker
+    StopMarker,
+    Other,
+}
+
+/// An object placed along a track, e.g. a signal or a sign
+#[derive(Debug, Clone)]
+pub struct TrackObject {
+    pub id: i32,
+    pub kind: TrackObjectKind,
+    /// Name of the prefab used for the object
+    pub prefab: String,
+    pub pos: Vec3,
+    pub rotation: Mat3,
+    pub track_id: Option<i32>,
+    /// Line of the "TrackObject" row
+    pub line: usize,
+}
+
+impl TrackObject {
+    pub fn is_signal(&self) -> bool {
+        matches!(self.kind, TrackObjectKind::Signal { .. })
+    }
+
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        self.pos = transform.apply_to_pos(self.pos);
+        self.rotation = transform.apply_to_rotation(self.rotation);
+    }
+}
+
+fn classify(prefab: &str, param: &str) -> TrackObjectKind {
+    if regex_is_match!(r"^(Sem|Tm|To)", prefab) {
+        TrackObjectKind::Signal { name: param.to_string() }
+    } else if regex_is_match!(r"^W[ _-]?8(\D|$)", prefab) {
+        TrackObjectKind::SpeedSign { speed: param.parse().ok() }
+    } else if regex_is_match!(r"(?i)^(km|hm|slupek)", prefab) {
+        TrackObjectKind::KilometrePost { kilometre: param.replace(',', ".").parse().ok() }
+    } else if regex_is_match!(r"^W[ _-]?4(\D|$)", prefab) {
+        TrackObjectKind::StopMarker
+    } else {
+        TrackObjectKind::Other
+    }
+}
+
+// TrackObject;id;prefab;x;y;z;rot x;rot y;rot z;track id;param
+pub(crate) fn parse_track_object(cells: &[&str], line: usize) -> anyhow::Result<TrackObject> {
+    ensure!(cells.len() >= 10);
+    let id = cells[1].parse()?;
+    let prefab = cells[2].to_string();
+    let pos = parse_position(&cells[3..6])?;
+    let rotation = parse_transform(&cells[6..9])?;
+    let track_id = if cells[9].is_empty() { None } else { Some(cells[9].parse()?) };
+    let param = cells.get(10).copied().unwrap_or_default();
+
+    Ok(TrackObject {
+        id,
+        kind: classify(&prefab, param),
+        prefab,
+        pos,
+        rotation,
+        track_id,
+        line,
+    })
+}
@@ -0,0 +1,133 @@
+use crate::parse::{ParseResult, Track};
+use crate::track_graph::{Edge, ForkSide, TrackGraph};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Position a switch has to be in for a train to follow a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchPosition {
+    /// Line of the "TrackStructure" row of the switch
+    pub line: Option<usize>,
+    /// ID of the track on the facing side of the fork
+    pub facing_track_id: i32,
+    /// ID of the branch the switch has to be set to
+    pub branch_track_id: i32,
+}
+
+/// A path through the track graph, in the order of travel
+#[derive(Debug, Clone)]
+pub struct TrackPath {
+    pub edges: Vec<Edge>,
+    pub track_ids: Vec<i32>,
+    pub switch_positions: Vec<SwitchPosition>,
+    /// Total length, including the first and last track
+    pub length: f32,
+}
+
+impl TrackPath {
+    pub fn contains(&self, track_index: usize) -> bool {
+        self.edges.iter().any(|x| x.track_index == track_index)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct QueueEntry {
+    length: f32,
+    edge: Edge,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    // Reversed, so that `BinaryHeap` pops the shortest length first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.length.total_cmp(&self.length)
+    }
+}
+
+/// Returns the switch set by moving from `from` to `to`, if the move goes through a fork
+fn switch_position(graph: &TrackGraph, tracks: &[Track], from: Edge, to: Edge) -> Option<SwitchPosition> {
+    let (facing, branch) = match (graph.fork_side(from.arrival()), graph.fork_side(to.departure())) {
+        (Some(ForkSide::Facing), _) => (from, to),
+        (Some(ForkSide::Trailing), Some(ForkSide::Facing)) => (to, from),
+        _ => return None,
+    };
+    let facing_track = &tracks[facing.track_index];
+    Some(SwitchPosition {
+        line: facing_track.line,
+        facing_track_id: facing_track.ids.own,
+        branch_track_id: tracks[branch.track_index].ids.own,
+    })
+}
+
+/// Finds the shortest path between two tracks that a train can follow without reversing,
+/// starting in either direction.
+pub fn find_path(graph: &TrackGraph, tracks: &[Track], from_index: usize, to_index: usize) -> Option<TrackPath> {
+    let mut lengths: HashMap<Edge, f32> = HashMap::new();
+    let mut previous: HashMap<Edge, Edge> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    for edge in graph.edges().filter(|x| x.track_index == from_index) {
+        let length = tracks[from_index].length();
+        lengths.insert(edge, length);
+        queue.push(QueueEntry { length, edge });
+    }
+
+    let mut last_edge = None;
+    while let Some(QueueEntry { length, edge }) = queue.pop() {
+        if lengths.get(&edge).is_some_and(|x| *x < length) {
+            continue;
+        }
+        if edge.track_index == to_index {
+            last_edge = Some(edge);
+            break;
+        }
+        for next in graph.next_edges(edge) {
+            let next_length = length + tracks[next.track_index].length();
+            if lengths.get(&next).is_none_or(|x| next_length < *x) {
+                lengths.insert(next, next_length);
+                previous.insert(next, edge);
+                queue.push(QueueEntry { length: next_length, edge: next });
+            }
+        }
+    }
+
+    let last_edge = last_edge?;
+    let mut edges = vec![last_edge];
+    while let Some(edge) = previous.get(edges.last().unwrap()) {
+        edges.push(*edge);
+    }
+    edges.reverse();
+
+    let switch_positions = edges
+        .windows(2)
+        .filter_map(|x| switch_position(graph, tracks, x[0], x[1]))
+        .collect();
+    Some(TrackPath {
+        track_ids: edges.iter().map(|x| tracks[x.track_index].ids.own).collect(),
+        switch_positions,
+        length: lengths[&last_edge],
+        edges,
+    })
+}
+
+impl ParseResult {
+    /// Finds the shortest path a train can take between the tracks with the given IDs
+    pub fn find_path(&self, from_id: i32, to_id: i32) -> Option<TrackPath> {
+        let from_index = *self.track_indexes.get(&from_id)?;
+        let to_index = *self.track_indexes.get(&to_id)?;
+        find_path(&self.track_graph(), &self.tracks, from_index, to_index)
+    }
+}
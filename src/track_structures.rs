@@ -1,12 +1,17 @@
 use crate::track_structures::TrackStructure::{Fork, Slip};
+use anyhow::{bail, Context};
 use lazy_regex::regex_captures;
-use phf::phf_map;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct ForkSwitch {
     pub(crate) radius_1: f32,
     pub(crate) radius_2: f32,
@@ -17,7 +22,8 @@ pub(crate) struct ForkSwitch {
     pub(crate) added_length: f32,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct SlipSwitch {
     pub(crate) total_length: f32,
     pub(crate) outer_length: f32,
@@ -28,20 +34,38 @@ pub(crate) struct SlipSwitch {
     pub(crate) right_slip: bool,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct Crossing {
     pub(crate) length: f32,
     pub(crate) tangent_inv: f32,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub(crate) enum TrackStructure {
     Fork(ForkSwitch),
     Slip(SlipSwitch),
     Crossing(Crossing),
 }
 
-pub fn parse_track_structure_prefabs(path: &Path) -> anyhow::Result<()> {
+/// Result of extracting the track structures from a directory of prefabs
+#[derive(Debug)]
+pub struct PrefabExtraction {
+    pub catalogue: TrackStructureCatalogue,
+    /// Names of the prefabs without a track structure
+    pub skipped: Vec<String>,
+    /// Names of the prefabs with a track structure which couldn't be extracted, with the reason.
+    /// Their structures are copied from the reference catalogue, see `copied`.
+    pub failed: Vec<(String, String)>,
+    /// Names of the failed prefabs whose structures were copied from the reference catalogue
+    pub copied: Vec<String>,
+}
+
+/// Extracts the track structures from the ".prefab" files in the directory.
+/// The values the prefabs don't store, and the structures which can't be extracted at all,
+/// are copied from the structures with the same names in `reference` and listed in the notes.
+pub fn extract_catalogue(path: &Path, reference: &TrackStructureCatalogue) -> anyhow::Result<PrefabExtraction> {
     let mut candidates: Vec<(PathBuf, String)> = vec![];
 
     for entry in path.read_dir()? {
@@ -51,7 +75,7 @@ pub fn parse_track_structure_prefabs(path: &Path) -> anyhow::Result<()> {
             .expect("Failed to get file type")
             .is_file()
         {
-            if !entry.path().extension().is_some_and(|x| x == "prefab") {
+            if entry.path().extension().is_none_or(|x| x != "prefab") {
                 continue;
             }
             if let Some(name) = entry
@@ -64,22 +88,41 @@ pub fn parse_track_structure_prefabs(path: &Path) -> anyhow::Result<()> {
         }
     }
 
-    candidates.sort_by_key(|(_, name)| name.clone());
-
+    let source = match &reference.game_version {
+        Some(version) => format!("the catalogue {version}"),
+        None => "the reference catalogue".to_string(),
+    };
+    candidates.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut catalogue = TrackStructureCatalogue::default();
+    let mut skipped = vec![];
+    let mut failed = vec![];
+    let mut copied = vec![];
     for (path, name) in candidates {
-        let is_right = name.ends_with('R');
-        let prefab = parse_prefab(&path, is_right)?;
-        if let Some(switch) = prefab {
-            println!("\"{}\" => {:?},", name, switch);
-        } else {
-            println!("Failed to extract switch info from prefab {}", name);
+        match parse_prefab(&path, &name, reference.get(&name)) {
+            Ok(Some(structure)) => {
+                if let Slip(_) = structure {
+                    catalogue.notes.push(format!(
+                        "`transition_length` of {name} copied from {source}, as the prefabs don't store it"
+                    ));
+                }
+                catalogue.structures.insert(name, structure);
+            },
+            Ok(None) => skipped.push(name),
+            Err(e) => {
+                if let Some(structure) = reference.get(&name) {
+                    catalogue.notes.push(format!("{name} copied from {source}, as it couldn't be extracted: {e}"));
+                    catalogue.structures.insert(name.clone(), *structure);
+                    copied.push(name.clone());
+                }
+                failed.push((name, e.to_string()));
+            },
         }
     }
 
-    Ok(())
+    Ok(PrefabExtraction { catalogue, skipped, failed, copied })
 }
 
-fn parse_prefab(path: &Path, is_right: bool) -> anyhow::Result<Option<TrackStructure>> {
+fn parse_prefab(path: &Path, name: &str, reference: Option<&TrackStructure>) -> anyhow::Result<Option<TrackStructure>> {
     let reader = BufReader::new(File::open(path)?);
     let lines_iter = reader
         .lines()
@@ -88,7 +131,7 @@ fn parse_prefab(path: &Path, is_right: bool) -> anyhow::Result<Option<TrackStruc
     let mut current: Vec<String> = vec![];
     for line in lines_iter {
         if line.starts_with("--- !u!") {
-            let switch = parse_prefab_component(std::mem::take(&mut current), is_right);
+            let switch = parse_prefab_component(std::mem::take(&mut current), name, reference)?;
             if switch.is_some() {
                 return Ok(switch);
             }
@@ -96,14 +139,17 @@ fn parse_prefab(path: &Path, is_right: bool) -> anyhow::Result<Option<TrackStruc
             current.push(line);
         }
     }
-    Ok(parse_prefab_component(current, is_right))
+    parse_prefab_component(current, name, reference)
 }
 
-fn parse_prefab_component(lines: Vec<String>, is_right: bool) -> Option<TrackStructure> {
+fn parse_prefab_component(
+    lines: Vec<String>,
+    name: &str,
+    reference: Option<&TrackStructure>,
+) -> anyhow::Result<Option<TrackStructure>> {
     let mut lines_iter = lines.into_iter().peekable();
-    let first_line = lines_iter.next()?;
-    if first_line != "MonoBehaviour:" {
-        return None;
+    if lines_iter.next().is_none_or(|x| x != "MonoBehaviour:") {
+        return Ok(None);
     }
     let mut config: HashMap<String, String> = HashMap::new();
     for line in lines_iter {
@@ -112,15 +158,21 @@ fn parse_prefab_component(lines: Vec<String>, is_right: bool) -> Option<TrackStr
         }
     }
 
-    if let Some(switch) = try_parse_fork_switch(&config, is_right) {
-        return Some(Fork(switch));
+    if let Some(switch) = try_parse_fork_switch(&config, name.ends_with('R')) {
+        return Ok(Some(Fork(switch)));
+    }
+
+    if config.contains_key("doubleSwitchType") {
+        return Ok(Some(Slip(parse_slip_switch(&config, reference)?)));
     }
 
-    if let Some(switch) = try_parse_slip_switch(&config) {
-        return Some(Slip(switch));
+    if name.starts_with("Crossing") {
+        if let Some(crossing) = try_parse_crossing(&config) {
+            return Ok(Some(TrackStructure::Crossing(crossing)));
+        }
     }
 
-    None
+    Ok(None)
 }
 
 fn try_parse_fork_switch(config: &HashMap<String, String>, is_right: bool) -> Option<ForkSwitch> {
@@ -141,117 +193,399 @@ fn try_parse_fork_switch(config: &HashMap<String, String>, is_right: bool) -> Op
     })
 }
 
-fn try_parse_slip_switch(_config: &HashMap<String, String>) -> Option<SlipSwitch> {
-    unimplemented!();
-
-    // let (left_slip, right_slip) = match config.get("doubleSwitchType")?.as_str() {
-    //     "0" => (true, true),
-    //     "1" => (true, false),
-    //     _ => return None,
-    // };
-    // let length: f32 = config.get("length")?.parse().ok()?;
-    // let radius: f32 = config.get("radius")?.parse().ok()?;
-    // let tangent: f32 = config.get("tangent")?.parse().ok()?;
-    // Some(SlipSwitch {
-    //     radius,
-    //     total_length: length,
-    //     tangent,
-    //     left_slip,
-    //     right_slip,
-    // })
+/// Returns the value of the first key present in the config, parsed as a number
+fn get_number(config: &HashMap<String, String>, keys: &[&str]) -> Option<f32> {
+    keys.iter().find_map(|key| config.get(*key))?.parse().ok()
+}
+
+/// Returns the value of the key parsed as a number, or an error naming the key
+fn required_number(config: &HashMap<String, String>, key: &str) -> anyhow::Result<f32> {
+    let value = config.get(key).with_context(|| format!("Missing {key}"))?;
+    value.parse().with_context(|| format!("Invalid {key} {value}"))
+}
+
+/// Reads a slip switch from a component with `doubleSwitchType`, which also stores the total length,
+/// the radius and the tangent. The slip curves meet the straight tracks `radius * tan(angle / 2)` from the centre,
+/// which gives the length of the outer straights. Where the slip curves are split into the transition
+/// and centre tracks is not stored, so the transition length is copied from `reference`.
+fn parse_slip_switch(config: &HashMap<String, String>, reference: Option<&TrackStructure>) -> anyhow::Result<SlipSwitch> {
+    let (left_slip, right_slip) = match config["doubleSwitchType"].as_str() {
+        "0" => (true, true),
+        "1" => (true, false),
+        other => bail!("Unknown doubleSwitchType {other}"),
+    };
+    let total_length = required_number(config, "length")?;
+    let radius = required_number(config, "radius")?;
+    let tangent_inv = required_number(config, "tangent")?;
+    let half_angle = (1.0 / tangent_inv).atan() / 2.0;
+    let outer_length = total_length / 2.0 - radius * half_angle.tan();
+    let Some(Slip(reference)) = reference else {
+        bail!("The prefab doesn't store the transition length and there is no slip switch with the same name to copy it from");
+    };
+    Ok(SlipSwitch {
+        total_length,
+        outer_length,
+        transition_length: reference.transition_length,
+        radius,
+        tangent_inv,
+        left_slip,
+        right_slip,
+    })
+}
+
+/// Reads a crossing from a prefab named "Crossing...". The name is what identifies crossings,
+/// as components of other prefabs can have a length and a tangent without any radius too.
+fn try_parse_crossing(config: &HashMap<String, String>) -> Option<Crossing> {
+    let has_radius = ["radius", "radius1", "radius2"].iter().any(|x| config.contains_key(*x));
+    if has_radius || config.contains_key("doubleSwitchType") {
+        return None;
+    }
+    let length = get_number(config, &["length"])?;
+    let tangent_inv = get_number(config, &["tangent", "tan_alfa"])?;
+    Some(Crossing { length, tangent_inv })
+}
+
+impl TrackStructure {
+    fn kind_name(&self) -> &'static str {
+        match self {
+            Fork(_) => "fork switch",
+            Slip(_) => "slip switch",
+            TrackStructure::Crossing(_) => "crossing",
+        }
+    }
+
+    /// Named values of the structure, with flags as 0 or 1
+    fn fields(&self) -> Vec<(&'static str, f32)> {
+        let flag = |value: bool| if value { 1.0 } else { 0.0 };
+        match self {
+            Fork(fork) => vec![
+                ("radius_1", fork.radius_1),
+                ("radius_2", fork.radius_2),
+                ("curve_length", fork.curve_length),
+                ("tangent_inv", fork.tangent_inv),
+                ("added_length", fork.added_length),
+            ],
+            Slip(slip) => vec![
+                ("total_length", slip.total_length),
+                ("outer_length", slip.outer_length),
+                ("transition_length", slip.transition_length),
+                ("radius", slip.radius),
+                ("tangent_inv", slip.tangent_inv),
+                ("left_slip", flag(slip.left_slip)),
+                ("right_slip", flag(slip.right_slip)),
+            ],
+            TrackStructure::Crossing(crossing) => vec![
+                ("length", crossing.length),
+                ("tangent_inv", crossing.tangent_inv),
+            ],
+        }
+    }
+}
+
+/// Value of a field before and after a change of the catalogue
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: f32,
+    pub new: f32,
+}
+
+impl FieldChange {
+    pub fn delta(&self) -> f32 {
+        self.new - self.old
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StructureChange {
+    /// The structure is of another kind, e.g. a slip switch instead of a fork switch
+    Kind { old: &'static str, new: &'static str },
+    Fields(Vec<FieldChange>),
+}
+
+/// Differences between two catalogues, with the structures sorted by name
+#[derive(Debug, Clone, Default)]
+pub struct CatalogueDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<(String, StructureChange)>,
+    /// Structures of the new catalogue copied from the old one, instead of being compared to it
+    pub copied: Vec<String>,
+}
+
+impl CatalogueDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.copied.is_empty()
+    }
+
+    /// Moves the structures copied from the old catalogue to `copied`
+    pub fn with_copied(mut self, copied: &[String]) -> Self {
+        self.added.retain(|x| !copied.contains(x));
+        self.changed.retain(|(name, _)| !copied.contains(name));
+        self.copied = copied.to_vec();
+        self
+    }
+}
+
+impl Display for CatalogueDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for name in &self.added {
+            writeln!(f, "+ {name}")?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {name}")?;
+        }
+        for name in &self.copied {
+            writeln!(f, "= {name} (copied)")?;
+        }
+        for (name, change) in &self.changed {
+            writeln!(f, "~ {name}")?;
+            match change {
+                StructureChange::Kind { old, new } => writeln!(f, "    {old} -> {new}")?,
+                StructureChange::Fields(fields) => {
+                    for field in fields {
+                        writeln!(f, "    {}: {} -> {} ({:+})", field.field, field.old, field.new, field.delta())?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares the structures, returning `None` if they are the same
+fn structure_change(old: &TrackStructure, new: &TrackStructure) -> Option<StructureChange> {
+    if old.kind_name() != new.kind_name() {
+        return Some(StructureChange::Kind { old: old.kind_name(), new: new.kind_name() });
+    }
+    let fields: Vec<FieldChange> = old
+        .fields()
+        .into_iter()
+        .zip(new.fields())
+        .filter(|((_, old_value), (_, new_value))| (old_value - new_value).abs() > 1e-4)
+        .map(|((field, old), (_, new))| FieldChange { field, old, new })
+        .collect();
+    (!fields.is_empty()).then_some(StructureChange::Fields(fields))
+}
+
+/// Built-in catalogues, one for every game version with changed prefab geometry
+static BUILTIN_CATALOGUE_JSONS: &[&str] = &[
+    include_str!("../data/track_structures/2025.2.3.json"),
+];
+static BUILTIN_CATALOGUES: LazyLock<TrackStructureCatalogues> = LazyLock::new(|| {
+    BUILTIN_CATALOGUE_JSONS
+        .iter()
+        .try_fold(TrackStructureCatalogues { catalogues: vec![] }, |catalogues, json| {
+            catalogues.with_catalogue(TrackStructureCatalogue::from_json(json.as_bytes())?)
+        })
+        .expect("Built-in track structure catalogue is invalid")
+});
+
+/// Version of the game, e.g. "2025.2.3", compared part by part
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct GameVersion(Vec<u32>);
+
+impl FromStr for GameVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let parts = s
+            .split('.')
+            .map(|x| x.parse().with_context(|| format!("Invalid game version {s}")))
+            .collect::<anyhow::Result<Vec<u32>>>()?;
+        Ok(GameVersion(parts))
+    }
+}
+
+impl TryFrom<String> for GameVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> anyhow::Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<GameVersion> for String {
+    fn from(value: GameVersion) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+/// Track structures by prefab name, stored as JSON
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrackStructureCatalogue {
+    /// Game version the values were extracted from
+    #[serde(default)]
+    pub game_version: Option<GameVersion>,
+    /// Remarks about the values, e.g. which ones were corrected by hand
+    #[serde(default)]
+    pub notes: Vec<String>,
+    pub(crate) structures: BTreeMap<String, TrackStructure>,
+}
+
+impl TrackStructureCatalogue {
+    pub fn from_json<R: Read>(reader: R) -> anyhow::Result<Self> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Writes the catalogue with every structure on its own line, like the built-in files
+    pub fn to_json<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        writeln!(writer, "{{")?;
+        if let Some(game_version) = &self.game_version {
+            writeln!(writer, "  \"game_version\": {},", serde_json::to_string(game_version)?)?;
+        }
+        if self.notes.is_empty() {
+            writeln!(writer, "  \"notes\": [],")?;
+        } else {
+            writeln!(writer, "  \"notes\": [")?;
+            for (index, note) in self.notes.iter().enumerate() {
+                let separator = if index + 1 < self.notes.len() { "," } else { "" };
+                writeln!(writer, "    {}{separator}", serde_json::to_string(note)?)?;
+            }
+            writeln!(writer, "  ],")?;
+        }
+        writeln!(writer, "  \"structures\": {{")?;
+        for (index, (name, structure)) in self.structures.iter().enumerate() {
+            let separator = if index + 1 < self.structures.len() { "," } else { "" };
+            let value = serde_json::to_string(structure)?.replace(",\"", ", \"").replace("\":", "\": ");
+            let value = format!("{{ {} }}", &value[1..value.len() - 1]);
+            writeln!(writer, "    {}: {value}{separator}", serde_json::to_string(name)?)?;
+        }
+        writeln!(writer, "  }}")?;
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    /// Writes the structures in Rust syntax, one `"name" => Fork(ForkSwitch { .. }),` line each,
+    /// for reading the extracted values next to the code building the tracks from them
+    pub fn to_rust<W: Write>(&self, mut writer: W) -> anyhow::Result<()> {
+        for (name, structure) in &self.structures {
+            let value = match structure {
+                TrackStructure::Crossing(crossing) => format!("TrackStructure::Crossing({crossing:?})"),
+                _ => format!("{structure:?}"),
+            };
+            writeln!(writer, "{name:?} => {value},")?;
+        }
+        Ok(())
+    }
+
+    /// Lists the structures added, removed and changed in `new` compared to this catalogue
+    pub fn diff(&self, new: &TrackStructureCatalogue) -> CatalogueDiff {
+        let mut diff = CatalogueDiff::default();
+        for (name, structure) in &new.structures {
+            match self.structures.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(old) => {
+                    if let Some(change) = structure_change(old, structure) {
+                        diff.changed.push((name.clone(), change));
+                    }
+                },
+            }
+        }
+        diff.removed = self.structures.keys().filter(|x| !new.contains(x)).cloned().collect();
+        diff
+    }
+
+    /// Adds the structures of `overrides`, replacing the ones with the same names
+    pub fn with_overrides(mut self, overrides: TrackStructureCatalogue) -> Self {
+        self.structures.extend(overrides.structures);
+        self.notes.extend(overrides.notes);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.structures.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.structures.keys().map(String::as_str)
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&TrackStructure> {
+        self.structures.get(name)
+    }
+}
+
+/// Catalogues of several game versions, sorted from the oldest one
+#[derive(Debug, Clone)]
+pub struct TrackStructureCatalogues {
+    catalogues: Vec<TrackStructureCatalogue>,
+}
+
+impl Default for TrackStructureCatalogues {
+    fn default() -> Self {
+        Self::builtin()
+    }
 }
 
-// Values for prefabs extracted from game assets, version 2025.2.3.
-// `added_length` Rz 60E1-205-1_9 and Rz 60E1-265-1_10 fixed manually.
-// Crossings and slip switches were also added manually.
-pub(crate) static TRACK_STRUCTURES: phf::Map<&'static str, TrackStructure> = phf_map! {
-    // TODO: Verify direction
-    "Rkp 60E1-190-1_9 ab" => Slip(SlipSwitch { total_length: 33.165, outer_length: 6.06, transition_length: 7.461676, radius: 190.0, tangent_inv: 9.0, left_slip: false, right_slip: true }), // added manually
-    "Rkp 60E1-190-1_9 ba" => Slip(SlipSwitch { total_length: 33.165, outer_length: 6.06, transition_length: 7.461676, radius: 190.0, tangent_inv: 9.0, left_slip: false, right_slip: true }), // added manually
-    "Rkpd 60E1-190-1_9" => Slip(SlipSwitch { total_length: 33.165, outer_length: 6.06, transition_length: 7.461676, radius: 190.0, tangent_inv: 9.0, left_slip: true, right_slip: true }), // added manually
-    "Rld 60E1-1200_600-1_15 L" => Fork(ForkSwitch { radius_1: 600.0, radius_2: -1200.0, curve_length: 39.95566, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1200_600-1_15 R" => Fork(ForkSwitch { radius_1: -600.0, radius_2: 1200.0, curve_length: 39.95566, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1200_900-1_18.5 L" => Fork(ForkSwitch { radius_1: 900.0, radius_2: -1200.0, curve_length: 48.61317, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1200_900-1_18.5 R" => Fork(ForkSwitch { radius_1: -900.0, radius_2: 1200.0, curve_length: 48.61317, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_300-1_9 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: -1800.0, curve_length: 33.23108, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_300-1_9 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: 1800.0, curve_length: 33.23108, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_600-1_14 L" => Fork(ForkSwitch { radius_1: 600.0, radius_2: -1800.0, curve_length: 42.80262, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_600-1_14 R" => Fork(ForkSwitch { radius_1: -600.0, radius_2: 1800.0, curve_length: 42.80262, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_900-1_18.5 L" => Fork(ForkSwitch { radius_1: 900.0, radius_2: -1800.0, curve_length: 48.61317, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-1800_900-1_18.5 R" => Fork(ForkSwitch { radius_1: -900.0, radius_2: 1800.0, curve_length: 48.61317, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_1200-1_22 L" => Fork(ForkSwitch { radius_1: 1200.0, radius_2: -2500.0, curve_length: 54.51731, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_1200-1_22 R" => Fork(ForkSwitch { radius_1: -1200.0, radius_2: 2500.0, curve_length: 54.51731, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_250-1_8.5 L" => Fork(ForkSwitch { radius_1: 250.0, radius_2: -2500.0, curve_length: 29.31069, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_250-1_8.5 R" => Fork(ForkSwitch { radius_1: -250.0, radius_2: 2500.0, curve_length: 29.31069, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_400-1_10.5 L" => Fork(ForkSwitch { radius_1: 400.0, radius_2: -2500.0, curve_length: 38.00925, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_400-1_10.5 R" => Fork(ForkSwitch { radius_1: -400.0, radius_2: 2500.0, curve_length: 38.00925, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_600-1_14 L" => Fork(ForkSwitch { radius_1: 600.0, radius_2: -2500.0, curve_length: 42.80262, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_600-1_14 R" => Fork(ForkSwitch { radius_1: -600.0, radius_2: 2500.0, curve_length: 42.80262, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_900-1_17 L" => Fork(ForkSwitch { radius_1: 900.0, radius_2: -2500.0, curve_length: 52.14928, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-2500_900-1_17 R" => Fork(ForkSwitch { radius_1: -900.0, radius_2: 2500.0, curve_length: 52.14928, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-600_300-1_9 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: -600.0, curve_length: 29.92537, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-600_300-1_9 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: 600.0, curve_length: 29.92537, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-650_450-1_15 L" => Fork(ForkSwitch { radius_1: 450.0, radius_2: -650.0, curve_length: 29.96674, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-650_450-1_15 R" => Fork(ForkSwitch { radius_1: -450.0, radius_2: 650.0, curve_length: 29.96674, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-700_190-1_7.5 L" => Fork(ForkSwitch { radius_1: 190.0, radius_2: -700.0, curve_length: 25.22173, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-700_190-1_7.5 R" => Fork(ForkSwitch { radius_1: -190.0, radius_2: 700.0, curve_length: 25.22173, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-700_300-1_10 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: -700.0, curve_length: 29.38637, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-700_300-1_10 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: 700.0, curve_length: 29.38637, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-700_500-1_14 L" => Fork(ForkSwitch { radius_1: 500.0, radius_2: -700.0, curve_length: 35.66885, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-700_500-1_14 R" => Fork(ForkSwitch { radius_1: -500.0, radius_2: 700.0, curve_length: 35.66885, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-900_300-1_9 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: -900.0, curve_length: 33.23108, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-900_300-1_9 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: 900.0, curve_length: 33.23108, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-900_450-1_12 L" => Fork(ForkSwitch { radius_1: 450.0, radius_2: -900.0, curve_length: 37.43512, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-900_450-1_12 R" => Fork(ForkSwitch { radius_1: -450.0, radius_2: 900.0, curve_length: 37.43512, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rld 60E1-900_600-1_15 L" => Fork(ForkSwitch { radius_1: 600.0, radius_2: -900.0, curve_length: 39.95566, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rld 60E1-900_600-1_15 R" => Fork(ForkSwitch { radius_1: -600.0, radius_2: 900.0, curve_length: 39.95566, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlds 60E1-1000-1000-1_23" => Fork(ForkSwitch { radius_1: 1000.0, radius_2: -1000.0, curve_length: 43.45773, tangent_inv: 22.0, added_length: 0.0 }),
-    "Rlds 60E1-190-190-1_9" => Fork(ForkSwitch { radius_1: 190.0, radius_2: -190.0, curve_length: 21.23, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlds 60E1-600-600-1_18.5" => Fork(ForkSwitch { radius_1: 599.9205, radius_2: -600.0, curve_length: 33.22262, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-1200_300-1_7 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 1200.0, curve_length: 42.64069, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1200_300-1_7 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: -1200.0, curve_length: 42.64069, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1200_600-1_9 L" => Fork(ForkSwitch { radius_1: 600.0, radius_2: 1200.0, curve_length: 68.0, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1200_600-1_9 R" => Fork(ForkSwitch { radius_1: -600.0, radius_2: -1200.0, curve_length: 68.0, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1500_450-1_9 L" => Fork(ForkSwitch { radius_1: 450.0, radius_2: 1500.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1500_450-1_9 R" => Fork(ForkSwitch { radius_1: -450.0, radius_2: -1500.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_300-1_7.5 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 1800.0, curve_length: 39.0, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_300-1_7.5 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: -1800.0, curve_length: 39.0, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_450-1_9 L" => Fork(ForkSwitch { radius_1: 450.0, radius_2: 1800.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_450-1_9 R" => Fork(ForkSwitch { radius_1: -450.0, radius_2: -1800.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_600-1_10 L" => Fork(ForkSwitch { radius_1: 599.48096, radius_2: 1800.0, curve_length: 59.85075, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-1800_600-1_10 R" => Fork(ForkSwitch { radius_1: -599.48096, radius_2: -1800.0, curve_length: 59.85075, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-600_300-1_6 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 600.0, curve_length: 48.0, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-600_300-1_6 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: -600.0, curve_length: 48.0, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-650_190-1_20 L" => Fork(ForkSwitch { radius_1: 190.0, radius_2: 650.0, curve_length: 32.47971, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-650_190-1_20 R" => Fork(ForkSwitch { radius_1: -190.0, radius_2: -650.0, curve_length: 32.47971, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-750_190-1_6 L" => Fork(ForkSwitch { radius_1: 190.0, radius_2: 750.0, curve_length: 31.44976, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-750_190-1_6 R" => Fork(ForkSwitch { radius_1: -190.0, radius_2: -750.0, curve_length: 31.44976, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-800_250-1_6.5 L" => Fork(ForkSwitch { radius_1: 250.0, radius_2: 800.0, curve_length: 38.23661, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-800_250-1_6.5 R" => Fork(ForkSwitch { radius_1: -250.0, radius_2: -800.0, curve_length: 38.23661, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-900_300-1_7.5 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 900.0, curve_length: 39.82378, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-900_300-1_7.5 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: -900.0, curve_length: 39.82378, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-900_300-1_9 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 900.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-900_300-1_9 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: -900.0, curve_length: 49.84663, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rlj 60E1-900_450-1_7.5 L" => Fork(ForkSwitch { radius_1: 450.0, radius_2: 900.0, curve_length: 59.0, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rlj 60E1-900_450-1_7.5 R" => Fork(ForkSwitch { radius_1: -450.0, radius_2: -900.0, curve_length: 59.0, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rz 60E1-1200-1_18.5 L" => Fork(ForkSwitch { radius_1: 1200.0, radius_2: 0.0, curve_length: 64.81756, tangent_inv: 18.5, added_length: 0.0 }),
-    "Rz 60E1-1200-1_18.5 R" => Fork(ForkSwitch { radius_1: -1200.0, radius_2: 0.0, curve_length: 64.81756, tangent_inv: 18.5, added_length: 0.0 }),
-    "Rz 60E1-190-1_7.5 L" => Fork(ForkSwitch { radius_1: 190.0, radius_2: 0.0, curve_length: 25.221731, tangent_inv: 7.5, added_length: 0.0 }),
-    "Rz 60E1-190-1_7.5 R" => Fork(ForkSwitch { radius_1: -190.0, radius_2: 0.0, curve_length: 25.221731, tangent_inv: 7.5, added_length: 0.0 }),
-    "Rz 60E1-190-1_9 L" => Fork(ForkSwitch { radius_1: 0.0, radius_2: 190.0, curve_length: 21.046352, tangent_inv: 9.0, added_length: 6.0923653 }),
-    "Rz 60E1-190-1_9 R" => Fork(ForkSwitch { radius_1: 0.0, radius_2: -190.0, curve_length: 21.046352, tangent_inv: 9.0, added_length: 6.0923653 }),
-    "Rz 60E1-205-1_9 L" => Fork(ForkSwitch { radius_1: 205.0, radius_2: 0.0, curve_length: 22.707907, tangent_inv: 9.0, added_length: 5.42 /* added manually */ }),
-    "Rz 60E1-205-1_9 R" => Fork(ForkSwitch { radius_1: -205.0, radius_2: 0.0, curve_length: 22.707907, tangent_inv: 9.0, added_length: 5.42 /* added manually */ }),
-    "Rz 60E1-2500-1_26.5 L" => Fork(ForkSwitch { radius_1: 2500.0, radius_2: 0.0, curve_length: 94.30607, tangent_inv: 26.5, added_length: 0.0 }),
-    "Rz 60E1-2500-1_26.5 R" => Fork(ForkSwitch { radius_1: -2500.0, radius_2: 0.0, curve_length: 94.30607, tangent_inv: 26.5, added_length: 0.0 }),
-    "Rz 60E1-265-1_10 L" => Fork(ForkSwitch { radius_1: 0.0, radius_2: 265.0, curve_length: 26.434078, tangent_inv: 10.0, added_length: 4.75 /* added manually */ }),
-    "Rz 60E1-265-1_10 R" => Fork(ForkSwitch { radius_1: 0.0, radius_2: -265.0, curve_length: 26.434078, tangent_inv: 10.0, added_length: 4.75 /* added manually */ }),
-    "Rz 60E1-300-1_9 L" => Fork(ForkSwitch { radius_1: 300.0, radius_2: 0.0, curve_length: 33.231083, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rz 60E1-300-1_9 R" => Fork(ForkSwitch { radius_1: -300.0, radius_2: 0.0, curve_length: 33.231083, tangent_inv: 9.0, added_length: 0.0 }),
-    "Rz 60E1-500-1_12 L" => Fork(ForkSwitch { radius_1: 500.0, radius_2: 0.0, curve_length: 41.59458, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rz 60E1-500-1_12 R" => Fork(ForkSwitch { radius_1: -500.0, radius_2: 0.0, curve_length: 41.59458, tangent_inv: 12.0, added_length: 0.0 }),
-    "Rz 60E1-760-1_14 L" => Fork(ForkSwitch { radius_1: 760.0, radius_2: 0.0, curve_length: 54.21665, tangent_inv: 14.0, added_length: 0.0 }),
-    "Rz 60E1-760-1_14 R" => Fork(ForkSwitch { radius_1: -760.0, radius_2: 0.0, curve_length: 54.21665, tangent_inv: 14.0, added_length: 0.0 }),
-    "Crossing" => TrackStructure::Crossing(Crossing { length: 33.2294 /* added manually */, tangent_inv: 9.0 }),
-    "Crossing4.444" => TrackStructure::Crossing(Crossing { length: 20.0 /* added manually */, tangent_inv: 4.444 }),
-};
\ No newline at end of file
+impl TrackStructureCatalogues {
+    pub fn builtin() -> Self {
+        BUILTIN_CATALOGUES.clone()
+    }
+
+    /// Adds the catalogue of another game version, replacing the one with the same version
+    pub fn with_catalogue(mut self, catalogue: TrackStructureCatalogue) -> anyhow::Result<Self> {
+        let Some(version) = &catalogue.game_version else {
+            bail!("The catalogue has no game version");
+        };
+        match self.catalogues.binary_search_by(|x| x.game_version.as_ref().cmp(&Some(version))) {
+            Ok(index) => self.catalogues[index] = catalogue,
+            Err(index) => self.catalogues.insert(index, catalogue),
+        }
+        Ok(self)
+    }
+
+    /// Adds the structures of `overrides` to the catalogues of all versions
+    pub fn with_overrides(mut self, overrides: TrackStructureCatalogue) -> Self {
+        self.catalogues = self.catalogues
+            .into_iter()
+            .map(|x| x.with_overrides(overrides.clone()))
+            .collect();
+        self
+    }
+
+    pub fn versions(&self) -> impl Iterator<Item = &GameVersion> {
+        self.catalogues.iter().filter_map(|x| x.game_version.as_ref())
+    }
+
+    pub fn latest(&self) -> &TrackStructureCatalogue {
+        self.catalogues.last().expect("No track structure catalogues")
+    }
+
+    /// Returns the newest catalogue not newer than `version`, or the oldest one if all are newer
+    pub fn for_version(&self, version: &GameVersion) -> &TrackStructureCatalogue {
+        self.catalogues
+            .iter()
+            .rev()
+            .find(|x| x.game_version.as_ref().is_some_and(|x| x <= version))
+            .unwrap_or(&self.catalogues[0])
+    }
+
+    /// Catalogues defining all the structures, from the newest one
+    pub fn matching<'a>(&self, structure_names: impl IntoIterator<Item = &'a str>) -> Vec<&TrackStructureCatalogue> {
+        let structure_names: Vec<&str> = structure_names.into_iter().collect();
+        self.catalogues
+            .iter()
+            .rev()
+            .filter(|catalogue| structure_names.iter().all(|x| catalogue.contains(x)))
+            .collect()
+    }
+
+    /// Guesses the game version of a scenery from the names of its track structures,
+    /// as the scenery files don't store it. Picks the newest catalogue defining all of them,
+    /// which is only right for older sceneries if their structures changed since.
+    pub fn detect<'a>(&self, structure_names: impl IntoIterator<Item = &'a str>) -> &TrackStructureCatalogue {
+        self.matching(structure_names).first().copied().unwrap_or_else(|| self.latest())
+    }
+}
@@ -0,0 +1,38 @@
+use glam::{Mat3, Vec3};
+
+/// Rotation followed by a translation
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Mat3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { translation: Vec3::ZERO, rotation: Mat3::IDENTITY };
+
+    pub fn new(translation: Vec3, rotation: Mat3) -> Self {
+        Transform { translation, rotation }
+    }
+
+    pub fn apply_to_pos(&self, pos: Vec3) -> Vec3 {
+        self.rotation * pos + self.translation
+    }
+
+    pub fn apply_to_rotation(&self, rotation: Mat3) -> Mat3 {
+        self.rotation * rotation
+    }
+
+    /// Returns the transform equivalent to applying `inner` first and then `self`
+    pub fn then_apply(&self, inner: &Transform) -> Transform {
+        Transform {
+            translation: self.apply_to_pos(inner.translation),
+            rotation: self.apply_to_rotation(inner.rotation),
+        }
+    }
+}
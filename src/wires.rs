@@ -0,0 +1,93 @@
+use crate::parse::{parse_position, Track, TrackShape};
+use crate::transform::Transform;
+use anyhow::ensure;
+use glam::{Vec3, Vec3Swizzles};
+
+/// Maximum horizontal distance between a wire and the track below it
+static MAX_HORIZONTAL_DISTANCE: f32 = 2.0;
+/// Allowed range of the wire height above the track
+static MIN_WIRE_HEIGHT: f32 = 3.0;
+static MAX_WIRE_HEIGHT: f32 = 9.0;
+
+/// A span of the overhead line between two supports
+#[derive(Debug, Clone)]
+pub struct CatenarySpan {
+    pub id: i32,
+    pub kind: String,
+    pub start: Vec3,
+    pub end: Vec3,
+    /// Line of the "Wires" row
+    pub line: usize,
+    /// IDs of the tracks running under the span
+    pub track_ids: Vec<i32>,
+}
+
+impl CatenarySpan {
+    pub(crate) fn transform(&mut self, transform: &Transform) {
+        self.start = transform.apply_to_pos(self.start);
+        self.end = transform.apply_to_pos(self.end);
+    }
+
+    /// Returns the wire height above `pos`, if `pos` is close enough horizontally to the span
+    fn height_above(&self, pos: Vec3) -> Option<f32> {
+        let span = self.end.xz() - self.start.xz();
+        let length_squared = span.length_squared();
+        let t = if length_squared > 0.0 {
+            ((pos.xz() - self.start.xz()).dot(span) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let closest = self.start.lerp(self.end, t);
+        if (closest.xz() - pos.xz()).length() > MAX_HORIZONTAL_DISTANCE {
+            return None;
+        }
+        Some(closest.y - pos.y)
+    }
+
+    fn is_above(&self, pos: Vec3) -> bool {
+        self.height_above(pos)
+            .is_some_and(|height| (MIN_WIRE_HEIGHT..=MAX_WIRE_HEIGHT).contains(&height))
+    }
+}
+
+// Wires;id;kind;x1;y1;z1;x2;y2;z2
+pub(crate) fn parse_wires(cells: &[&str], line: usize) -> anyhow::Result<CatenarySpan> {
+    ensure!(cells.len() >= 9);
+    Ok(CatenarySpan {
+        id: cells[1].parse()?,
+        kind: cells[2].to_string(),
+        start: parse_position(&cells[3..6])?,
+        end: parse_position(&cells[6..9])?,
+        line,
+        track_ids: vec![],
+    })
+}
+
+/// Points checked for being under the wires: both ends and the middle of the track
+fn check_points(shape: &TrackShape) -> Vec<Vec3> {
+    match shape {
+        TrackShape::Point(point) => vec![point.pos],
+        _ => vec![shape.start().pos, shape.midpoint(), shape.end().pos],
+    }
+}
+
+/// Marks the tracks which run under the catenary along their whole length as electrified
+/// and records the matched tracks on each span.
+pub(crate) fn match_wires(spans: &mut [CatenarySpan], tracks: &mut [Track]) {
+    for track in tracks {
+        let points = check_points(&track.shape);
+        let mut electrified = true;
+        for point in &points {
+            let matching_spans: Vec<&mut CatenarySpan> = spans.iter_mut().filter(|x| x.is_above(*point)).collect();
+            if matching_spans.is_empty() {
+                electrified = false;
+            }
+            for span in matching_spans {
+                if !span.track_ids.contains(&track.ids.own) {
+                    span.track_ids.push(track.ids.own);
+                }
+            }
+        }
+        track.electrified = electrified;
+    }
+}
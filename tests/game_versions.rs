@@ -0,0 +1,42 @@
+use td2_map::parse::{parse_with_options, ParseOptions};
+use td2_map::track_structures::{TrackStructureCatalogue, TrackStructureCatalogues};
+
+const OLDER_CATALOGUE: &str = r#"{ "game_version": "2024.1", "structures": {
+    "Rld 60E1-1200_600-1_15 L": { "type": "fork", "radius_1": 600.0, "radius_2": -1200.0, "curve_length": 39.95566, "tangent_inv": 12.0, "added_length": 0.0 }
+} }"#;
+
+const SCENERY: &str = "TrackStructure;100;Rld 60E1-1200_600-1_15 L;0;0;0;0;0;0;11::,12::,13::,14::,15::;;;;;;;;;\r\n";
+
+fn options() -> ParseOptions {
+    let older = TrackStructureCatalogue::from_json(OLDER_CATALOGUE.as_bytes()).unwrap();
+    ParseOptions {
+        catalogues: TrackStructureCatalogues::builtin().with_catalogue(older).unwrap(),
+        ..Default::default()
+    }
+}
+
+fn has_guess_warning(options: &ParseOptions) -> bool {
+    let parse_result = parse_with_options(SCENERY.as_bytes(), options).unwrap();
+    parse_result.diagnostics.iter().any(|x| x.message.contains("guessed"))
+}
+
+#[test]
+fn guessed_version() {
+    let options = options();
+    assert!(has_guess_warning(&options));
+    let parse_result = parse_with_options(SCENERY.as_bytes(), &options).unwrap();
+    assert_eq!(parse_result.game_version, Some(options.catalogues.latest().game_version.clone().unwrap()));
+}
+
+#[test]
+fn given_version() {
+    let options = ParseOptions { game_version: Some("2024.1".parse().unwrap()), ..options() };
+    assert!(!has_guess_warning(&options));
+    let parse_result = parse_with_options(SCENERY.as_bytes(), &options).unwrap();
+    assert_eq!(parse_result.game_version, Some("2024.1".parse().unwrap()));
+}
+
+#[test]
+fn single_matching_version() {
+    assert!(!has_guess_warning(&ParseOptions::default()));
+}
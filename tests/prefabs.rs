@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::PathBuf;
+use td2_map::track_structures::{extract_catalogue, StructureChange, TrackStructureCatalogue, TrackStructureCatalogues};
+
+const SLIP_PREFAB: &str = "%YAML 1.1
+%TAG !u! tag:unity3d.com,2011:
+--- !u!1 &1
+GameObject:
+  m_Name: Rkpd 60E1-190-1_9
+--- !u!114 &2
+MonoBehaviour:
+  m_Enabled: 1
+  doubleSwitchType: 0
+  length: 33.165
+  radius: 190
+  tangent: 9
+";
+
+/// Creates a directory with the slip switch prefab, named after the test
+fn prefab_dir(test_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("td2_map_{test_name}"));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("Rkpd 60E1-190-1_9.prefab"), SLIP_PREFAB).unwrap();
+    dir
+}
+
+#[test]
+fn slip_switch() {
+    let dir = prefab_dir("slip_switch");
+    let builtin = TrackStructureCatalogues::builtin();
+    let extraction = extract_catalogue(&dir, builtin.latest()).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    assert!(extraction.failed.is_empty());
+    assert_eq!(extraction.catalogue.names().collect::<Vec<_>>(), ["Rkpd 60E1-190-1_9"]);
+    assert_eq!(extraction.catalogue.notes.len(), 1);
+
+    // The hand-written outer length is rounded, all other values are the same
+    let diff = builtin.latest().diff(&extraction.catalogue);
+    let [(name, StructureChange::Fields(fields))] = &diff.changed[..] else {
+        panic!("Unexpected changes: {diff}");
+    };
+    assert_eq!(name, "Rkpd 60E1-190-1_9");
+    assert_eq!(fields.len(), 1);
+    assert_eq!(fields[0].field, "outer_length");
+    assert!(fields[0].delta().abs() < 0.001);
+}
+
+#[test]
+fn slip_switch_without_reference() {
+    let dir = prefab_dir("slip_switch_without_reference");
+    let extraction = extract_catalogue(&dir, &TrackStructureCatalogue::default()).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    assert_eq!(extraction.catalogue.names().count(), 0);
+    assert_eq!(extraction.failed.len(), 1);
+    assert_eq!(extraction.failed[0].0, "Rkpd 60E1-190-1_9");
+}
+
+#[test]
+fn failed_prefab_is_copied() {
+    let dir = prefab_dir("failed_prefab_is_copied");
+    fs::write(dir.join("Rkp 60E1-190-1_9 ab.prefab"), SLIP_PREFAB.replace("doubleSwitchType: 0", "doubleSwitchType: 7")).unwrap();
+    let builtin = TrackStructureCatalogues::builtin();
+    let extraction = extract_catalogue(&dir, builtin.latest()).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    assert_eq!(extraction.failed.len(), 1);
+    assert_eq!(extraction.copied, ["Rkp 60E1-190-1_9 ab"]);
+    assert_eq!(extraction.catalogue.notes.len(), 2);
+
+    let diff = builtin.latest().diff(&extraction.catalogue).with_copied(&extraction.copied);
+    assert_eq!(diff.copied, extraction.copied);
+    assert!(!diff.removed.contains(&extraction.copied[0]));
+
+    // The output can be added as the catalogue of a game version
+    let mut catalogue = extraction.catalogue;
+    catalogue.game_version = Some("2026.1".parse().unwrap());
+    let mut json = vec![];
+    catalogue.to_json(&mut json).unwrap();
+    let catalogues = builtin.clone().with_catalogue(TrackStructureCatalogue::from_json(json.as_slice()).unwrap()).unwrap();
+    assert_eq!(catalogues.latest().names().count(), 2);
+}
@@ -0,0 +1,76 @@
+use td2_map::parse::{parse, parse_with_options, ParseOptions, ParseResult};
+use td2_map::repair::{propose_repairs, RepairAction};
+use td2_map::track_structures::{TrackStructureCatalogue, TrackStructureCatalogues};
+
+/// Applies the proposed repairs and parses the repaired scenery again
+fn repair(input: &str) -> (Vec<RepairAction>, ParseResult) {
+    let mut parse_result = parse(input.as_bytes()).unwrap();
+    assert!(!parse_result.failed_connections.is_empty());
+    let repairs = propose_repairs(&parse_result);
+    parse_result.apply_repairs(&repairs).unwrap();
+    let mut output = vec![];
+    parse_result.write(&mut output).unwrap();
+    let actions = repairs.into_iter().map(|x| x.action).collect();
+    (actions, parse(output.as_slice()).unwrap())
+}
+
+fn cells(parse_result: &ParseResult, line: usize) -> Vec<String> {
+    parse_result.rows.iter().find(|x| x.line == Some(line)).unwrap().cells.clone()
+}
+
+#[test]
+fn set_length() {
+    let input = "Track;1;Track;0;0;0;0;0;0;98;0;2;;;;;;;;;;\r\n\
+        Track;2;Track;0;0;100;0;0;0;50;0;;1;;;;;;;;;\r\n";
+    let (actions, repaired) = repair(input);
+    assert!(matches!(actions[..], [RepairAction::SetLength { track_id: 1, .. }]));
+    assert!(repaired.failed_connections.is_empty());
+    assert_eq!(cells(&repaired, 1)[9], "100");
+}
+
+#[test]
+fn move_end() {
+    // Only the third track has a free end, the other two are linked at both ends
+    let input = "Track;1;Track;0;0;0;0;0;0;100;0;2;5;;;;;;;;;\r\n\
+        Track;2;Track;0;0;100;0;0;0;50;0;;1;;;;;;;;;\r\n\
+        Track;5;Track;-3;0;-40;0;0;0;39;0;1;;;;;;;;;;\r\n";
+    let (actions, repaired) = repair(input);
+    assert!(matches!(actions[..], [RepairAction::MoveEnd { track_id: 5, at_end: true, .. }]));
+    assert!(repaired.failed_connections.is_empty());
+    assert_eq!(cells(&repaired, 3)[3..6], ["0", "0", "-39"]);
+}
+
+#[test]
+fn insert_connector() {
+    // The first track is rolled and the second one is an arc, so neither can be changed
+    let input = "Track;1;Track;0;0;0;0;0;5;90;0;2;;zwr;80;1;iso1;T1;x;;;;\r\n\
+        Track;2;Track;0;0;100;0;0;0;50;300;;1;zwr;60;0;iso2;T2;;;;;\r\n";
+    let (actions, repaired) = repair(input);
+    assert!(matches!(actions[..], [RepairAction::InsertConnector { track_id: 3, prev_id: 1, next_id: 2, .. }]));
+    assert!(repaired.failed_connections.is_empty());
+    assert_eq!(repaired.tracks.len(), 3);
+
+    let connector = cells(&repaired, 2);
+    assert_eq!(connector[..3], ["Track", "3", "BTrack"]);
+    assert_eq!(connector[15..17], ["2", "1"]);
+    // The attributes are copied from the first track
+    assert_eq!(connector[17..], cells(&repaired, 1)[13..]);
+    assert_eq!(cells(&repaired, 1)[11], "3");
+    assert_eq!(cells(&repaired, 3)[12], "3");
+}
+
+#[test]
+fn structure_joints_are_not_repaired() {
+    // The slip is longer than its curves, so its outer tracks don't meet the slip curves
+    let overrides = r#"{ "structures": { "Long slip": { "type": "slip", "total_length": 36.0, "outer_length": 6.06,
+        "transition_length": 7.461676, "radius": 190.0, "tangent_inv": 9.0, "left_slip": true, "right_slip": true } } }"#;
+    let options = ParseOptions {
+        catalogues: TrackStructureCatalogues::builtin().with_overrides(TrackStructureCatalogue::from_json(overrides.as_bytes()).unwrap()),
+        ..Default::default()
+    };
+    let input = "TrackStructure;100;Long slip;0;0;0;0;0;0;\
+        11::,12::,13::,14::,15::,16::,17::,18::,19::,20::,21::,22::,23::,24::,25::,26::;;;;;;;;;\r\n";
+    let parse_result = parse_with_options(input.as_bytes(), &options).unwrap();
+    assert!(!parse_result.failed_connections.is_empty());
+    assert!(propose_repairs(&parse_result).is_empty());
+}
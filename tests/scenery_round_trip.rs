@@ -0,0 +1,88 @@
+use td2_map::diagnostics::Severity;
+use td2_map::parse::parse;
+
+fn round_trip(input: &[u8]) -> Vec<u8> {
+    let parse_result = parse(input).unwrap();
+    let mut output = vec![];
+    parse_result.write(&mut output).unwrap();
+    output
+}
+
+const ROWS: [&str; 6] = [
+    "Track;1;Track;0;0;0;0;0;0;100;0;;2;;;;;;;;;",
+    "Track;2;Track;0;0;100;0;0;0;50;0;1;;;;;;;;;;",
+    "SomethingNew;1;2;;x;",
+    "",
+    "Fence;3;0;0;0",
+    "TerrainPoint;0;1.50;0",
+];
+
+#[test]
+fn lf() {
+    let input = ROWS.join("\n") + "\n";
+    assert_eq!(round_trip(input.as_bytes()), input.as_bytes());
+}
+
+#[test]
+fn crlf() {
+    let input = ROWS.join("\r\n") + "\r\n";
+    assert_eq!(round_trip(input.as_bytes()), input.as_bytes());
+}
+
+#[test]
+fn no_trailing_newline() {
+    let input = ROWS.join("\r\n");
+    assert_eq!(round_trip(input.as_bytes()), input.as_bytes());
+}
+
+#[test]
+fn mixed_line_endings() {
+    let input = format!("{}\r\n{}\n{}", ROWS[0], ROWS[1], ROWS[2]);
+    assert_eq!(round_trip(input.as_bytes()), input.as_bytes());
+}
+
+#[test]
+fn invalid_utf8() {
+    let mut input = b"Misc;5;".to_vec();
+    // "ąć" in CP1250
+    input.extend([0xb9, 0xe6]);
+    input.extend(b";0;0;0\r\n");
+    input.extend(ROWS[0].as_bytes());
+    input.extend(b"\r\n");
+    assert_eq!(round_trip(&input), input);
+}
+
+#[test]
+fn invalid_utf8_rows_are_parsed() {
+    let mut input = ROWS[0].as_bytes().to_vec();
+    // Invalid bytes in the last cell
+    input.extend([0xb9, 0xe6]);
+    input.extend(b"\n");
+
+    let parse_result = parse(input.as_slice()).unwrap();
+    assert_eq!(parse_result.tracks.len(), 1);
+    let warnings: Vec<_> = parse_result.diagnostics.iter().filter(|x| x.message.contains("UTF-8")).collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert_eq!(warnings[0].line, Some(1));
+    assert_eq!(round_trip(&input), input);
+}
+
+#[test]
+fn only_modified_rows_change() {
+    let mut input = b"Misc;5;".to_vec();
+    input.extend([0xb9, 0xe6]);
+    input.extend(b";0;0;0\n");
+    input.extend(ROWS[0].as_bytes());
+    input.extend(b"\n");
+
+    let mut parse_result = parse(input.as_slice()).unwrap();
+    parse_result.row_mut(2).unwrap().set_cell(9, "120");
+    let mut output = vec![];
+    parse_result.write(&mut output).unwrap();
+
+    let mut expected = input[..input.iter().position(|x| *x == b'\n').unwrap() + 1].to_vec();
+    expected.extend(ROWS[0].replacen(";100;", ";120;", 1).as_bytes());
+    expected.extend(b"\n");
+    assert_eq!(output, expected);
+}